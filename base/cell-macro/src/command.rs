@@ -0,0 +1,176 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::punctuated::Punctuated;
+use syn::{
+    AttributeArgs, FnArg, Ident, ItemFn, Lit, LitStr, Meta, NestedMeta, Path, ReturnType, Token,
+    Type,
+};
+
+struct CommandArgs {
+    protocol: LitStr,
+    method: Option<String>,
+    asy: bool,
+    // description/request_schema/response_schema, fed into the command catalogue
+    docs: Vec<(Ident, LitStr)>,
+}
+
+fn parse_args(args: AttributeArgs) -> syn::Result<CommandArgs> {
+    let mut protocol: Option<LitStr> = None;
+    let mut method: Option<String> = None;
+    let mut asy = false;
+    let mut docs = Vec::new();
+    for arg in args {
+        match arg {
+            NestedMeta::Meta(Meta::NameValue(nv)) => {
+                let key = nv
+                    .path
+                    .get_ident()
+                    .map(|v| v.to_string())
+                    .unwrap_or_default();
+                match (key.as_str(), &nv.lit) {
+                    ("protocol", Lit::Str(v)) => protocol = Some(v.clone()),
+                    ("method", Lit::Str(v)) => method = Some(v.value().to_uppercase()),
+                    ("asy", Lit::Bool(v)) => asy = v.value,
+                    ("description", Lit::Str(v))
+                    | ("request_schema", Lit::Str(v))
+                    | ("response_schema", Lit::Str(v)) => {
+                        docs.push((format_ident!("with_{}", key), v.clone()))
+                    }
+                    _ => {
+                        return Err(syn::Error::new_spanned(
+                            nv,
+                            "unknown cell_command argument, expected protocol/method/asy/description/request_schema/response_schema",
+                        ))
+                    }
+                }
+            }
+            other => {
+                return Err(syn::Error::new_spanned(
+                    other,
+                    "cell_command arguments must be `key = value`",
+                ))
+            }
+        }
+    }
+    let protocol = protocol.ok_or_else(|| {
+        syn::Error::new(
+            Span::call_site(),
+            "cell_command requires `protocol = \"...\"`",
+        )
+    })?;
+    Ok(CommandArgs {
+        protocol,
+        method,
+        asy,
+        docs,
+    })
+}
+
+fn run_type(method: &Option<String>) -> syn::Result<TokenStream> {
+    match method.as_deref() {
+        None | Some("ANY") => Ok(quote!(::cell_core::core::runTypeHttp)),
+        Some("GET") => Ok(quote!(::cell_core::core::runTypeHttpGet)),
+        Some("POST") => Ok(quote!(::cell_core::core::runTypeHttpPost)),
+        Some(v) => Err(syn::Error::new(
+            Span::call_site(),
+            format!("unsupported method:{}, expected GET/POST/ANY", v),
+        )),
+    }
+}
+
+fn is_result(ty: &Type) -> bool {
+    if let Type::Path(p) = ty {
+        if let Some(seg) = p.path.segments.last() {
+            return seg.ident == "CellResult" || seg.ident == "Result";
+        }
+    }
+    false
+}
+
+pub fn expand_command(args: AttributeArgs, func: ItemFn) -> syn::Result<TokenStream> {
+    let args = parse_args(args)?;
+    let run_type = run_type(&args.method)?;
+    let protocol = args.protocol;
+    let asy = args.asy;
+    let docs = args.docs.iter().map(|(f, v)| quote!(.#f(#v)));
+
+    if func.sig.asyncness.is_some() {
+        return Err(syn::Error::new_spanned(
+            func.sig.fn_token,
+            "cell_command does not support async fn",
+        ));
+    }
+    let name = &func.sig.ident;
+    let vis = &func.vis;
+    let cmd_name = format_ident!("{}_command", name);
+
+    let inputs: Vec<&FnArg> = func.sig.inputs.iter().collect();
+    let call = match inputs.len() {
+        0 => quote!(#name()),
+        1 => match inputs[0] {
+            FnArg::Typed(pt) => {
+                let ty = &pt.ty;
+                quote! {
+                    match ::cell_core::cell_macro::decode_input::<#ty>(ctx) {
+                        ::std::result::Result::Ok(input) => #name(input),
+                        ::std::result::Result::Err(e) => {
+                            ::cell_core::cell_macro::fire_error(ctx, e);
+                            return;
+                        }
+                    }
+                }
+            }
+            FnArg::Receiver(r) => {
+                return Err(syn::Error::new_spanned(
+                    r,
+                    "cell_command can not be used on methods",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &func.sig.inputs,
+                "cell_command takes at most one typed input",
+            ))
+        }
+    };
+
+    let ret = match &func.sig.output {
+        ReturnType::Type(_, ty) if is_result(ty) => call,
+        ReturnType::Type(_, _) => quote!(::std::result::Result::Ok(#call)),
+        ReturnType::Default => {
+            return Err(syn::Error::new_spanned(
+                &func.sig,
+                "cell_command must return a Serializable output",
+            ))
+        }
+    };
+
+    Ok(quote! {
+        #func
+
+        #vis fn #cmd_name() -> ::cell_core::command::Command<'static> {
+            ::cell_core::command::Command::default()
+                .with_protocol_id(#protocol)
+                .with_run_type(#run_type)
+                .with_meta_data(::cell_core::command::MetaData::default().with_asy(#asy)#(#docs)*)
+                .with_executor(::std::sync::Arc::new(::cell_core::command::ClosureFunc::new(
+                    ::std::sync::Arc::new(|ctx, _v| {
+                        let ret = #ret;
+                        ::cell_core::cell_macro::fire_result(ctx, ret);
+                    }),
+                )))
+        }
+    })
+}
+
+pub fn expand_commands(paths: Punctuated<Path, Token![,]>) -> TokenStream {
+    let cmds = paths.iter().map(|p| {
+        let mut p = p.clone();
+        if let Some(last) = p.segments.last_mut() {
+            last.ident = format_ident!("{}_command", last.ident);
+        }
+        quote!(#p())
+    });
+    quote!(::std::option::Option::Some(::std::vec![#(#cmds),*]))
+}
@@ -0,0 +1,284 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{Attribute, Data, DeriveInput, Fields, Ident, Lit, LitStr, Meta, NestedMeta, Path};
+
+const LIFECYCLE: &[&str] = &["on_init", "on_start", "on_ready", "on_close"];
+
+struct ExtensionArgs {
+    module: Option<LitStr>,
+    id: Option<i16>,
+    log_level: Ident,
+    required: Option<bool>,
+    orderer: Option<i32>,
+    options: Option<Ident>,
+    components: Option<Ident>,
+    health_checks: Option<Ident>,
+    commands: Vec<Path>,
+    lifecycle: Vec<(Ident, Ident)>,
+}
+
+impl Default for ExtensionArgs {
+    fn default() -> Self {
+        ExtensionArgs {
+            module: None,
+            id: None,
+            log_level: Ident::new("Info", Span::call_site()),
+            required: None,
+            orderer: None,
+            options: None,
+            components: None,
+            health_checks: None,
+            commands: Vec::new(),
+            lifecycle: Vec::new(),
+        }
+    }
+}
+
+fn lit_ident(lit: &Lit) -> syn::Result<Ident> {
+    match lit {
+        Lit::Str(v) => v.parse::<Ident>(),
+        other => Err(syn::Error::new_spanned(
+            other,
+            "expected a method name string",
+        )),
+    }
+}
+
+fn parse_args(attrs: &[Attribute]) -> syn::Result<ExtensionArgs> {
+    let mut ret = ExtensionArgs::default();
+    for attr in attrs {
+        if !attr.path.is_ident("cell_extension") {
+            continue;
+        }
+        let list = match attr.parse_meta()? {
+            Meta::List(v) => v,
+            other => {
+                return Err(syn::Error::new_spanned(
+                    other,
+                    "expected #[cell_extension(...)]",
+                ))
+            }
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(nv)) => {
+                    let key = nv
+                        .path
+                        .get_ident()
+                        .map(|v| v.to_string())
+                        .unwrap_or_default();
+                    match (key.as_str(), &nv.lit) {
+                        ("module", Lit::Str(v)) => ret.module = Some(v.clone()),
+                        ("id", Lit::Int(v)) => ret.id = Some(v.base10_parse::<i16>()?),
+                        ("log_level", lit) => ret.log_level = lit_ident(lit)?,
+                        ("required", Lit::Bool(v)) => ret.required = Some(v.value),
+                        ("orderer", Lit::Int(v)) => ret.orderer = Some(v.base10_parse::<i32>()?),
+                        ("options", lit) => ret.options = Some(lit_ident(lit)?),
+                        ("components", lit) => ret.components = Some(lit_ident(lit)?),
+                        ("health_checks", lit) => ret.health_checks = Some(lit_ident(lit)?),
+                        (k, lit) if LIFECYCLE.contains(&k) => {
+                            let name = nv.path.get_ident().unwrap().clone();
+                            ret.lifecycle.push((name, lit_ident(lit)?));
+                        }
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                nv,
+                                "unknown cell_extension argument",
+                            ))
+                        }
+                    }
+                }
+                NestedMeta::Meta(Meta::List(l)) if l.path.is_ident("commands") => {
+                    for cmd in l.nested {
+                        match cmd {
+                            NestedMeta::Meta(Meta::Path(p)) => ret.commands.push(p),
+                            other => {
+                                return Err(syn::Error::new_spanned(
+                                    other,
+                                    "expected commands(fn_a, fn_b)",
+                                ))
+                            }
+                        }
+                    }
+                }
+                other => {
+                    return Err(syn::Error::new_spanned(
+                        other,
+                        "unknown cell_extension argument",
+                    ))
+                }
+            }
+        }
+    }
+    Ok(ret)
+}
+
+pub fn expand_extension(input: DeriveInput) -> syn::Result<TokenStream> {
+    let args = parse_args(&input.attrs)?;
+    let name = &input.ident;
+    let vis = &input.vis;
+    let factory = format_ident!("{}Factory", name);
+
+    let fields = match &input.data {
+        Data::Struct(s) => &s.fields,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "CellExtension can only be derived for structs",
+            ))
+        }
+    };
+
+    // #[component] fields are resolved from the collected components, the others use Default
+    let mut lookups = Vec::new();
+    let mut inits = Vec::new();
+    let construct = match fields {
+        Fields::Named(named) => {
+            for f in &named.named {
+                let ident = f.ident.as_ref().unwrap();
+                let ty = &f.ty;
+                if f.attrs.iter().any(|a| a.path.is_ident("component")) {
+                    let slot = format_ident!("__{}", ident);
+                    let component = quote!(#ty).to_string();
+                    let extension = name.to_string();
+                    // a missing component skips the extension instead of aborting the process
+                    lookups.push(quote! {
+                        let mut #slot: ::std::option::Option<#ty> = ::std::option::Option::None;
+                        for __component in _components.iter() {
+                            if let ::std::option::Option::Some(v) = __component.downcast_ref::<#ty>() {
+                                #slot = ::std::option::Option::Some(v.clone());
+                                break;
+                            }
+                        }
+                        let #slot: #ty = match #slot {
+                            ::std::option::Option::Some(v) => v,
+                            ::std::option::Option::None => {
+                                ::cell_core::cell_macro::missing_component(#extension, #component);
+                                return ::std::option::Option::None;
+                            }
+                        };
+                    });
+                    inits.push(quote!(#ident: #slot));
+                } else {
+                    inits.push(quote!(#ident: ::std::default::Default::default()));
+                }
+            }
+            quote!(#name { #(#inits),* })
+        }
+        Fields::Unit => quote!(#name),
+        Fields::Unnamed(_) => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "CellExtension requires named fields",
+            ))
+        }
+    };
+
+    // 和 ModuleEnumsStruct 一样由使用方分配 ,不能默认成同一个 id
+    let id = match args.id {
+        Some(v) => v,
+        None => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "cell_extension requires id = N ,a module id no other module uses",
+            ))
+        }
+    };
+    let module_name = args
+        .module
+        .unwrap_or_else(|| LitStr::new(&name.to_string().to_uppercase(), name.span()));
+    let log_level = &args.log_level;
+
+    let mut methods = Vec::new();
+    methods.push(quote! {
+        fn module(&self) -> ::cell_core::cell_macro::CellModule {
+            ::cell_core::cell_macro::CellModule::new(
+                #id,
+                #module_name,
+                &::cell_core::cell_macro::LogLevel::#log_level,
+            )
+        }
+    });
+    if let Some(required) = args.required {
+        methods.push(quote! {
+            fn required(&self) -> bool {
+                #required
+            }
+        });
+    }
+    if let Some(orderer) = args.orderer {
+        methods.push(quote! {
+            fn get_orderer(&mut self) -> i32 {
+                #orderer
+            }
+        });
+    }
+    if let Some(options) = &args.options {
+        methods.push(quote! {
+            fn get_options<'a>(&self) -> ::std::option::Option<::std::vec::Vec<::cell_core::cell_macro::Arg<'a>>> {
+                ::std::option::Option::Some(self.#options())
+            }
+        });
+    }
+    if let Some(checks) = &args.health_checks {
+        methods.push(quote! {
+            fn health_checks(&mut self) -> ::std::option::Option<::std::vec::Vec<::cell_core::health::HealthCheck>> {
+                ::std::option::Option::Some(self.#checks())
+            }
+        });
+    }
+    for (hook, target) in &args.lifecycle {
+        methods.push(quote! {
+            fn #hook(
+                &mut self,
+                ctx: ::std::sync::Arc<::std::cell::RefCell<::cell_core::extension::NodeContext>>,
+            ) -> ::cell_core::cerror::CellResult<()> {
+                self.#target(ctx)
+            }
+        });
+    }
+    if !args.commands.is_empty() {
+        let cmds = args.commands.iter().map(|p| {
+            let mut p = p.clone();
+            if let Some(last) = p.segments.last_mut() {
+                last.ident = format_ident!("{}_command", last.ident);
+            }
+            quote!(#p())
+        });
+        methods.push(quote! {
+            fn commands(&mut self) -> ::std::option::Option<::std::vec::Vec<::cell_core::command::Command<'static>>> {
+                ::std::option::Option::Some(::std::vec![#(#cmds),*])
+            }
+        });
+    }
+
+    let provided = match &args.components {
+        Some(f) => quote! {
+            fn components(&self) -> ::std::option::Option<::std::vec::Vec<::std::sync::Arc<::std::boxed::Box<dyn ::std::any::Any>>>> {
+                ::std::option::Option::Some(#name::#f())
+            }
+        },
+        None => quote!(),
+    };
+
+    Ok(quote! {
+        #vis struct #factory {}
+
+        impl ::cell_core::extension::ExtensionFactory for #factory {
+            fn build_extension(
+                &self,
+                _components: ::std::vec::Vec<::std::sync::Arc<::std::boxed::Box<dyn ::std::any::Any>>>,
+            ) -> ::std::option::Option<::std::sync::Arc<::std::cell::RefCell<dyn ::cell_core::extension::NodeExtension>>> {
+                #(#lookups)*
+                let ext = #construct;
+                ::std::option::Option::Some(::std::sync::Arc::new(::std::cell::RefCell::new(ext)))
+            }
+
+            #provided
+        }
+
+        impl ::cell_core::extension::NodeExtension for #name {
+            #(#methods)*
+        }
+    })
+}
@@ -0,0 +1,39 @@
+extern crate proc_macro;
+
+mod command;
+mod extension;
+
+use proc_macro::TokenStream;
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, AttributeArgs, DeriveInput, ItemFn, Path, Token};
+
+// #[cell_command(protocol = "/demo", method = "GET")]
+// keeps the function as it is and generates `<fn>_command() -> Command<'static>`
+#[proc_macro_attribute]
+pub fn cell_command(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as AttributeArgs);
+    let func = parse_macro_input!(item as ItemFn);
+    command::expand_command(args, func)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+// cell_commands!(demo, other) => Some(vec![demo_command(), other_command()])
+// meant to be the body of `NodeExtension::commands`
+#[proc_macro]
+pub fn cell_commands(input: TokenStream) -> TokenStream {
+    let paths = parse_macro_input!(input with Punctuated::<Path, Token![,]>::parse_terminated);
+    command::expand_commands(paths).into()
+}
+
+// #[derive(CellExtension)]
+// #[cell_extension(module = "DEMO", id = 100, on_start = "start", commands(demo))]
+// generates `<Struct>Factory` and the NodeExtension impl, fields marked with
+// #[component] are pulled from the collected components, the others use Default
+#[proc_macro_derive(CellExtension, attributes(cell_extension, component))]
+pub fn cell_extension(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    extension::expand_extension(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
@@ -1,8 +1,8 @@
 use crate::bus::{publish_application_events, subscribe_application_events, EventBus};
 use crate::command::Command;
 use crate::event::{
-    ApplicationEnvironmentPreparedEvent, ApplicationInitEvent, ApplicationReadyEvent,
-    ApplicationStartedEvent, Event, NextStepEvent,
+    ApplicationCloseEvent, ApplicationEnvironmentPreparedEvent, ApplicationInitEvent,
+    ApplicationReadyEvent, ApplicationStartedEvent, Event, NextStepEvent,
 };
 use crate::extension::{
     step_0, step_1, step_2, step_3, step_4, ExtensionFactory, ExtensionManager,
@@ -11,18 +11,20 @@ use crate::extension::{
 use crate::module::ModuleEnumsStruct;
 use core::any::Any;
 use core::cell::RefCell;
-use crossbeam::channel::{bounded, Select, Sender};
+use crossbeam::channel::{bounded, Receiver, Select, Sender};
 use flo_stream::Publisher;
 use logsdk::common::LogLevel;
 use logsdk::module::CellModule;
 use rocket::build;
 use std::sync::{Arc, Mutex};
 use std::thread::sleep;
+use std::time::Duration;
 use tokio::runtime::Runtime;
 use tokio::signal;
 use tokio::sync::mpsc;
 
 const Application: &'static str = "application";
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub struct CellApplication {
     bus: EventBus<Box<dyn Event>>,
@@ -33,6 +35,8 @@ pub struct CellApplication {
 
 impl CellApplication {
     pub fn run(self, args: Vec<String>) {
+        // hyper / rocket / tokio 的 log 与 tracing 输出统一走 logsdk
+        logsdk::bridge::install();
         let runtime = self.runtime.clone();
         runtime.block_on(async { self.async_start(args).await })
     }
@@ -40,7 +44,8 @@ impl CellApplication {
         // start
         self.bus.clone().start();
         self.manager.clone().start();
-        self.step0(args).await;
+        let sub = subscribe_application_events(self.bus.clone(), Application, None);
+        self.step0(args, sub.clone()).await;
 
         match signal::ctrl_c().await {
             Ok(()) => {}
@@ -52,10 +57,29 @@ impl CellApplication {
                 );
             }
         }
+        self.shutdown(sub).await;
     }
-    async fn step0(&self, args: Vec<String>) {
+
+    // graceful shutdown: extensions get ApplicationCloseEvent ,wait until they are all closed
+    async fn shutdown(&self, sub: Arc<Receiver<Arc<Box<dyn Event>>>>) {
+        publish_application_events(
+            Arc::new(self.bus.clone()),
+            Box::new(ApplicationCloseEvent::new()),
+            None,
+        );
+        // recv_timeout 会阻塞最多 SHUTDOWN_TIMEOUT ,不能占着 tokio 的 worker 线程
+        if let Err(e) = tokio::task::spawn_blocking(move || wait_closed(sub)).await {
+            cerror!(
+                ModuleEnumsStruct::CELL_APPLICATION,
+                "wait for extensions closing failed:{}",
+                e
+            );
+        }
+        logsdk::writer::shutdown_async_writer();
+    }
+
+    async fn step0(&self, args: Vec<String>, sub: Arc<Receiver<Arc<Box<dyn Event>>>>) {
         let app_bus = self.bus.clone();
-        let sub = subscribe_application_events(app_bus.clone(), Application, None);
         let arc_bus = Arc::new(app_bus.clone());
         let mut sel = Select::new();
         sel.recv(&sub);
@@ -103,7 +127,8 @@ impl CellApplication {
                                 None,
                             );
                         } else if v.current == step_3 {
-                            cinfo!(ModuleEnumsStruct::EXTENSION, "step:3")
+                            cinfo!(ModuleEnumsStruct::EXTENSION, "step:3");
+                            return;
                         } else if v.current == step_4 {
                             cinfo!(ModuleEnumsStruct::EXTENSION, "step:4")
                         }
@@ -155,6 +180,29 @@ impl CellApplication {
     }
 }
 
+fn wait_closed(sub: Arc<Receiver<Arc<Box<dyn Event>>>>) {
+    loop {
+        match sub.recv_timeout(SHUTDOWN_TIMEOUT) {
+            Ok(msg) => {
+                if let Some(v) = msg.as_any().downcast_ref::<NextStepEvent>() {
+                    if v.current == step_4 {
+                        cinfo!(ModuleEnumsStruct::CELL_APPLICATION, "application closed");
+                        return;
+                    }
+                }
+            }
+            Err(e) => {
+                cerror!(
+                    ModuleEnumsStruct::CELL_APPLICATION,
+                    "wait for extensions closing failed:{}",
+                    e
+                );
+                return;
+            }
+        }
+    }
+}
+
 fn collect_components(mut builders: &Vec<Box<dyn ExtensionFactory>>) -> Vec<Arc<Box<dyn Any>>> {
     let mut ret: Vec<Arc<Box<dyn Any>>> = Vec::new();
     for i in 0..builders.len() {
@@ -203,6 +203,11 @@ where
         self.runtime.clone().spawn(self.do_start());
     }
 
+    // commands waiting for the bus loop
+    pub fn queue_depth(&self) -> usize {
+        self.cmds.len()
+    }
+
     pub fn publish(&self, msg: T, events: HashMap<String, Vec<String>>) {
         // TODO ,handle error
         let res = self.cmds.send(cmd {
@@ -0,0 +1,387 @@
+// 命令目录: 列出所有注册的command,支持 http 查询 / 命令行导出 / openapi
+use crate::cell_macro::fire_result;
+use crate::cerror::CellResult;
+use crate::command::{ClosureFunc, Command};
+use crate::core::{runTypeHttpGet, runTypeHttpPost, RunType};
+use crate::extension::{ExtensionFactory, NodeContext, NodeExtension};
+use crate::module::ModuleEnumsStruct;
+use crate::output::Serializable;
+use clap::{Arg, ArgMatches};
+use logsdk::common::LogLevel;
+use logsdk::module::CellModule;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+pub const CATALOGUE_PROTOCOL: &'static str = "/cell/commands";
+pub const OPENAPI_PROTOCOL: &'static str = "/cell/openapi";
+
+pub(crate) const DUMP_COMMANDS: &'static str = "dump-commands";
+pub(crate) const DUMP_OPENAPI: &'static str = "dump-openapi";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CommandDescriptor {
+    pub protocol_id: String,
+    pub method: String,
+    pub asy: bool,
+    pub request_type: i8,
+    pub response_type: i8,
+    pub owner: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_schema: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_schema: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Catalogue {
+    pub commands: Vec<CommandDescriptor>,
+}
+
+impl<'a> Serializable<'a> for Catalogue {}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(transparent)]
+pub struct OpenApiDocument(pub Value);
+
+impl<'a> Serializable<'a> for OpenApiDocument {}
+
+fn method_of(run_type: RunType) -> &'static str {
+    if run_type == runTypeHttpGet {
+        "GET"
+    } else if run_type == runTypeHttpPost {
+        "POST"
+    } else {
+        "ANY"
+    }
+}
+
+fn parse_schema(protocol: &str, schema: Option<&'static str>) -> Option<Value> {
+    let schema = schema?;
+    match serde_json::from_str::<Value>(schema) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            cerror!(
+                ModuleEnumsStruct::CATALOGUE,
+                "illegal schema of command:{},err:{}",
+                protocol,
+                e
+            );
+            None
+        }
+    }
+}
+
+pub fn describe(cmd: &Command) -> CommandDescriptor {
+    let meta = &cmd.meta_data;
+    CommandDescriptor {
+        protocol_id: String::from(cmd.protocol_id),
+        method: String::from(method_of(cmd.run_type)),
+        asy: meta.asy,
+        request_type: meta.request_type,
+        response_type: meta.response_type,
+        owner: String::from(cmd.owner),
+        description: meta.description.map(String::from),
+        request_schema: parse_schema(cmd.protocol_id, meta.request_schema),
+        response_schema: parse_schema(cmd.protocol_id, meta.response_schema),
+    }
+}
+
+pub fn build_catalogue(commands: &HashMap<String, Command<'static>>) -> Catalogue {
+    let mut ret: Vec<CommandDescriptor> = commands.values().map(describe).collect();
+    ret.sort_by(|a, b| {
+        a.protocol_id
+            .cmp(&b.protocol_id)
+            .then(a.method.cmp(&b.method))
+    });
+    Catalogue { commands: ret }
+}
+
+fn openapi_operation(d: &CommandDescriptor, method: &str) -> Value {
+    let mut op = Map::new();
+    op.insert(
+        String::from("operationId"),
+        json!(format!("{}{}", method, d.protocol_id.replace('/', "_"))),
+    );
+    if !d.owner.is_empty() {
+        op.insert(String::from("tags"), json!([d.owner]));
+    }
+    if let Some(desc) = &d.description {
+        op.insert(String::from("summary"), json!(desc));
+    }
+    if let Some(schema) = &d.request_schema {
+        if method == "get" {
+            // get 请求的入参来自 query string
+            let required = schema.get("required").cloned().unwrap_or(json!([]));
+            let params: Vec<Value> = schema
+                .get("properties")
+                .and_then(|v| v.as_object())
+                .map(|props| {
+                    props
+                        .iter()
+                        .map(|(name, prop)| {
+                            json!({
+                                "name": name,
+                                "in": "query",
+                                "required": required.as_array().map_or(false, |r| r.contains(&json!(name))),
+                                "schema": prop,
+                            })
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            op.insert(String::from("parameters"), Value::Array(params));
+        } else {
+            op.insert(
+                String::from("requestBody"),
+                json!({"content": {"application/json": {"schema": schema}}}),
+            );
+        }
+    }
+    let mut success = json!({"description": "success"});
+    if let Some(schema) = &d.response_schema {
+        success["content"] = json!({"application/json": {"schema": schema}});
+    }
+    op.insert(
+        String::from("responses"),
+        json!({"200": success, "default": {"description": "failed"}}),
+    );
+    Value::Object(op)
+}
+
+pub fn build_openapi(catalogue: &Catalogue, title: &str, version: &str) -> Value {
+    let mut paths = Map::new();
+    for d in &catalogue.commands {
+        let methods: &[&str] = match d.method.as_str() {
+            "GET" => &["get"],
+            "POST" => &["post"],
+            _ => &["get", "post"],
+        };
+        let item = paths
+            .entry(d.protocol_id.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        for m in methods {
+            item[*m] = openapi_operation(d, m);
+        }
+    }
+    json!({
+        "openapi": "3.0.3",
+        "info": {"title": title, "version": version},
+        "paths": paths,
+    })
+}
+
+pub struct CatalogueExtensionFactory {}
+
+impl ExtensionFactory for CatalogueExtensionFactory {
+    fn build_extension(
+        &self,
+        compoents: Vec<Arc<Box<dyn Any>>>,
+    ) -> Option<Arc<RefCell<dyn NodeExtension>>> {
+        Some(Arc::new(RefCell::new(CatalogueExtension::new())))
+    }
+}
+
+pub struct CatalogueExtension {
+    catalogue: Arc<RwLock<Catalogue>>,
+}
+
+impl CatalogueExtension {
+    pub fn new() -> Self {
+        Self {
+            catalogue: Arc::new(RwLock::new(Catalogue::default())),
+        }
+    }
+
+    fn catalogue_command(&self) -> Command<'static> {
+        let catalogue = self.catalogue.clone();
+        let f = ClosureFunc::new(Arc::new(move |ctx, _| {
+            let ret = Catalogue {
+                commands: catalogue.read().unwrap().commands.clone(),
+            };
+            fire_result(ctx, Ok(ret))
+        }));
+        Command::default()
+            .with_protocol_id(CATALOGUE_PROTOCOL)
+            .with_run_type(runTypeHttpGet)
+            .with_executor(Arc::new(f))
+    }
+
+    fn openapi_command(&self) -> Command<'static> {
+        let catalogue = self.catalogue.clone();
+        let f = ClosureFunc::new(Arc::new(move |ctx, _| {
+            let doc = build_openapi(&catalogue.read().unwrap(), "rust-cell", "0.1.0");
+            fire_result(ctx, Ok(OpenApiDocument(doc)))
+        }));
+        Command::default()
+            .with_protocol_id(OPENAPI_PROTOCOL)
+            .with_run_type(runTypeHttpGet)
+            .with_executor(Arc::new(f))
+    }
+}
+
+// the document asked for by --dump-openapi / --dump-commands ,None when neither is given
+// called by the extension manager in step 0 ,before any extension is initialized
+pub fn dump_document(
+    matchers: &ArgMatches,
+    commands: &HashMap<String, Command<'static>>,
+) -> Option<Value> {
+    if matchers.is_present(DUMP_OPENAPI) {
+        Some(build_openapi(
+            &build_catalogue(commands),
+            "rust-cell",
+            "0.1.0",
+        ))
+    } else if matchers.is_present(DUMP_COMMANDS) {
+        Some(serde_json::to_value(&build_catalogue(commands)).unwrap())
+    } else {
+        None
+    }
+}
+
+impl NodeExtension for CatalogueExtension {
+    fn module(&self) -> CellModule {
+        ModuleEnumsStruct::CATALOGUE.clone()
+    }
+
+    fn required(&self) -> bool {
+        false
+    }
+
+    fn get_options<'a>(&self) -> Option<Vec<Arg<'a>>> {
+        Some(vec![
+            Arg::new(DUMP_COMMANDS)
+                .long(DUMP_COMMANDS)
+                .help("print the registered commands as json and exit")
+                .takes_value(false)
+                .required(false),
+            Arg::new(DUMP_OPENAPI)
+                .long(DUMP_OPENAPI)
+                .help("print the openapi document of the registered commands and exit")
+                .takes_value(false)
+                .required(false),
+        ])
+    }
+
+    // commands are collected before on_start, so the catalogue is complete here
+    fn on_start(&mut self, ctx: Arc<RefCell<NodeContext>>) -> CellResult<()> {
+        let ctx = ctx.borrow();
+        let catalogue = build_catalogue(&ctx.commands);
+        cinfo!(
+            ModuleEnumsStruct::CATALOGUE,
+            "collected {} commands",
+            catalogue.commands.len()
+        );
+
+        *self.catalogue.write().unwrap() = catalogue;
+        Ok(())
+    }
+
+    fn commands(&mut self) -> Option<Vec<Command<'static>>> {
+        Some(vec![self.catalogue_command(), self.openapi_command()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::catalogue::{build_catalogue, build_openapi, dump_document, CatalogueExtension};
+    use crate::command::{mock_context, Command, CommandTrait, MetaData};
+    use crate::core::{runTypeHttp, runTypeHttpGet, runTypeHttpPost};
+    use crate::extension::NodeExtension;
+    use clap::App;
+    use std::collections::HashMap;
+
+    fn commands() -> HashMap<String, Command<'static>> {
+        let mut ret = HashMap::new();
+        ret.insert(
+            String::from("/user-5"),
+            Command::default()
+                .with_protocol_id("/user")
+                .with_run_type(runTypeHttpGet)
+                .with_owner("DEMO")
+                .with_meta_data(
+                    MetaData::default()
+                        .with_description("query user")
+                        .with_request_schema(
+                            r#"{"type":"object","properties":{"id":{"type":"integer"}},"required":["id"]}"#,
+                        )
+                        .with_response_schema(r#"{"type":"object"}"#),
+                ),
+        );
+        ret.insert(
+            String::from("/echo-1"),
+            Command::default()
+                .with_protocol_id("/echo")
+                .with_run_type(runTypeHttp)
+                .with_owner("DEMO")
+                .with_meta_data(MetaData::default().with_asy(true)),
+        );
+        ret.insert(
+            String::from("/user-3"),
+            Command::default()
+                .with_protocol_id("/user")
+                .with_run_type(runTypeHttpPost)
+                .with_meta_data(MetaData::default().with_request_schema("not json")),
+        );
+        ret
+    }
+
+    #[test]
+    fn test_build_catalogue() {
+        let c = build_catalogue(&commands());
+        assert_eq!(c.commands.len(), 3);
+        assert_eq!(c.commands[0].protocol_id, "/echo");
+        assert_eq!(c.commands[0].method, "ANY");
+        assert!(c.commands[0].asy);
+        assert_eq!(c.commands[1].method, "GET");
+        assert_eq!(c.commands[1].owner, "DEMO");
+        assert_eq!(c.commands[1].description.as_deref(), Some("query user"));
+        assert!(c.commands[2].request_schema.is_none());
+    }
+
+    #[test]
+    fn test_build_openapi() {
+        let doc = build_openapi(&build_catalogue(&commands()), "rust-cell", "0.1.0");
+        assert_eq!(doc["openapi"], "3.0.3");
+        assert!(doc["paths"]["/echo"]["get"].is_object());
+        assert!(doc["paths"]["/echo"]["post"].is_object());
+        let get = &doc["paths"]["/user"]["get"];
+        assert_eq!(get["summary"], "query user");
+        assert_eq!(get["parameters"][0]["name"], "id");
+        assert_eq!(get["parameters"][0]["required"], true);
+        assert!(doc["paths"]["/user"]["post"]["requestBody"].is_null());
+    }
+
+    #[test]
+    fn test_dump_document() {
+        let options = CatalogueExtension::new().get_options().unwrap();
+        let parse = |args: Vec<&str>| {
+            App::new("rust-cell")
+                .args(options.clone())
+                .get_matches_from(args)
+        };
+        assert!(dump_document(&parse(vec!["rust-cell"]), &commands()).is_none());
+        let doc = dump_document(&parse(vec!["rust-cell", "--dump-commands"]), &commands());
+        assert_eq!(doc.unwrap()["commands"].as_array().unwrap().len(), 3);
+        let doc = dump_document(&parse(vec!["rust-cell", "--dump-openapi"]), &commands());
+        assert_eq!(doc.unwrap()["openapi"], "3.0.3");
+    }
+
+    #[test]
+    fn test_catalogue_command() {
+        let mut ext = CatalogueExtension::new();
+        *ext.catalogue.write().unwrap() = build_catalogue(&commands());
+        let cmds = ext.commands().unwrap();
+        let (_, rxx, mut ctx) = mock_context();
+        cmds[0].execute(&mut ctx);
+        let resp = rxx.recv().unwrap();
+        let body = futures::executor::block_on(hyper::body::to_bytes(resp.into_body())).unwrap();
+        let v: serde_json::Value = serde_json::from_slice(body.as_ref()).unwrap();
+        assert_eq!(v["commands"].as_array().unwrap().len(), 3);
+    }
+}
@@ -1,3 +1,213 @@
 // command 过程宏+ 属性宏
+// the proc macros live in the `cell-macro` crate, the helpers they expand to live here
+pub use ::cell_macro::{cell_command, cell_commands, CellExtension};
+// re-exported so the generated extension code does not require clap/logsdk paths in the user crate
+pub use clap::Arg;
+pub use logsdk::common::LogLevel;
+pub use logsdk::module::CellModule;
 
-//
+use crate::cerror::{CellError, CellResult};
+use crate::constants::ProtocolStatus;
+use crate::context::BuzzContextTrait;
+use crate::input::from_request;
+use crate::module::ModuleEnumsStruct;
+use crate::output::{as_json_bytes, Serializable};
+use crate::wrapper::ContextResponseWrapper;
+use bytes::Bytes;
+use serde::de::DeserializeOwned;
+
+// called by the generated `build_extension` before it returns None
+pub fn missing_component(extension: &str, component: &str) {
+    cerror!(
+        ModuleEnumsStruct::EXTENSION,
+        "component {} required by extension {} not found ,the extension is skipped",
+        component,
+        extension
+    );
+}
+
+pub fn decode_input<'a, T: DeserializeOwned>(ctx: &mut dyn BuzzContextTrait<'a>) -> CellResult<T> {
+    let req = ctx.get_request();
+    from_request::<T>(&**req)
+}
+
+pub fn fire_result<'a, T: Serializable<'static>>(
+    ctx: &mut dyn BuzzContextTrait<'a>,
+    ret: CellResult<T>,
+) {
+    match ret.and_then(|v| as_json_bytes(v)) {
+        Ok(body) => {
+            let resp = ContextResponseWrapper::default()
+                .with_status(ProtocolStatus::SUCCESS)
+                .with_body(body);
+            if let Err(e) = ctx.response(resp) {
+                cerror!(ModuleEnumsStruct::COMMAND, "response failed:{}", e);
+            }
+        }
+        Err(e) => fire_error(ctx, e),
+    }
+}
+
+pub fn fire_error<'a>(ctx: &mut dyn BuzzContextTrait<'a>, err: CellError) {
+    cerror!(ModuleEnumsStruct::COMMAND, "execute command failed:{}", err);
+    let resp = ContextResponseWrapper::default()
+        .with_status(ProtocolStatus::FAIL)
+        .with_body(Bytes::from(err.get_msg().clone()));
+    if let Err(e) = ctx.response(resp) {
+        cerror!(ModuleEnumsStruct::COMMAND, "response failed:{}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::EventBus;
+    use crate::cell_macro::{cell_command, cell_commands, Arg, CellExtension};
+    use crate::cerror::CellResult;
+    use crate::command::{mock_context, Command, CommandTrait};
+    use crate::core::{runTypeHttp, runTypeHttpGet};
+    use crate::extension::{ExtensionFactory, NodeContext};
+    use crate::health::{HealthCheck, HealthIndicator};
+    use crate::output::Serializable;
+    use serde::{Deserialize, Serialize};
+    use std::any::Any;
+    use std::cell::RefCell;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use tokio::runtime::Runtime;
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct DemoResponse {
+        name: String,
+    }
+
+    impl<'a> Serializable<'a> for DemoResponse {}
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct EchoRequest {
+        #[serde(default)]
+        name: String,
+    }
+
+    #[cell_command(protocol = "/demo", method = "GET")]
+    fn demo() -> CellResult<DemoResponse> {
+        Ok(DemoResponse {
+            name: String::from("charlie"),
+        })
+    }
+
+    #[cell_command(protocol = "/echo", description = "echo the name back")]
+    fn echo(input: EchoRequest) -> DemoResponse {
+        DemoResponse { name: input.name }
+    }
+
+    fn commands() -> Option<Vec<Command<'static>>> {
+        cell_commands!(demo, echo)
+    }
+
+    #[test]
+    fn test_cell_command() {
+        let cmd = demo_command();
+        assert_eq!(cmd.protocol_id, "/demo");
+        assert_eq!(cmd.run_type, runTypeHttpGet);
+
+        let (_, rxx, mut ctx) = mock_context();
+        cmd.execute(&mut ctx);
+        let resp = rxx.recv().unwrap();
+        let body = futures::executor::block_on(hyper::body::to_bytes(resp.into_body())).unwrap();
+        assert_eq!(body.as_ref(), br#"{"name":"charlie"}"#);
+    }
+
+    #[test]
+    fn test_cell_commands() {
+        let cmds = commands().unwrap();
+        assert_eq!(cmds.len(), 2);
+        assert_eq!(cmds[1].protocol_id, "/echo");
+        assert_eq!(cmds[1].run_type, runTypeHttp);
+        assert_eq!(cmds[1].meta_data.description, Some("echo the name back"));
+    }
+
+    #[derive(Clone, Debug)]
+    pub struct DemoComponent {
+        name: String,
+        // shared with the test ,set by do_init
+        inited: Arc<AtomicBool>,
+    }
+
+    #[derive(CellExtension)]
+    #[cell_extension(
+        module = "DEMO",
+        id = 100,
+        required = false,
+        orderer = 3,
+        options = "options",
+        components = "provide",
+        on_init = "do_init",
+        health_checks = "checks",
+        commands(demo, echo)
+    )]
+    pub struct DemoExtension {
+        #[component]
+        com: DemoComponent,
+        inited: bool,
+    }
+
+    impl DemoExtension {
+        fn provide() -> Vec<Arc<Box<dyn Any>>> {
+            vec![Arc::new(Box::new(DemoComponent {
+                name: String::from("demo"),
+                inited: Default::default(),
+            }))]
+        }
+
+        fn options<'a>(&self) -> Vec<Arg<'a>> {
+            vec![Arg::new("demo").long("demo").required(false)]
+        }
+
+        fn checks(&mut self) -> Vec<HealthCheck> {
+            let name = self.com.name.clone();
+            vec![Arc::new(move || {
+                HealthIndicator::up().with_detail("component", name.as_str())
+            })]
+        }
+
+        fn do_init(&mut self, _ctx: Arc<RefCell<NodeContext>>) -> CellResult<()> {
+            self.inited = true;
+            self.com.inited.store(self.inited, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_cell_extension() {
+        let factory = DemoExtensionFactory {};
+        assert_eq!(factory.components().unwrap().len(), 1);
+        // 缺少 #[component] 时不构建 ,也不 panic
+        assert!(factory.build_extension(vec![]).is_none());
+
+        let inited: Arc<AtomicBool> = Default::default();
+        let supplied: Arc<Box<dyn Any>> = Arc::new(Box::new(DemoComponent {
+            name: String::from("supplied"),
+            inited: inited.clone(),
+        }));
+        let ext = factory.build_extension(vec![supplied]).unwrap();
+        let mut ext = ext.borrow_mut();
+        assert_eq!(ext.module().get_name(), "DEMO");
+        assert_eq!(ext.module().to_string(), "module_name:DEMO,index:100");
+        assert!(!ext.required());
+        assert_eq!(ext.get_orderer(), 3);
+        assert_eq!(ext.get_options().unwrap().len(), 1);
+        assert_eq!(ext.commands().unwrap().len(), 2);
+        let health = ext.health_checks().unwrap()[0]();
+        assert!(health.up);
+        assert_eq!(health.details.get("component").unwrap(), "supplied");
+
+        let rt = Arc::new(Runtime::new().unwrap());
+        let ctx = Arc::new(RefCell::new(NodeContext::new(
+            rt.clone(),
+            EventBus::new(rt.clone()),
+        )));
+        assert!(!inited.load(Ordering::SeqCst));
+        ext.init(ctx).unwrap();
+        assert!(inited.load(Ordering::SeqCst));
+    }
+}
@@ -146,6 +146,8 @@ error_enums!(
     (DUPLICATE_STEP,9,"DUPLICATE_STEP");
     (EVENT_BUS_DUPLICATE_CLIENTID,10,"duplicate client id");
     (EVENT_BUS_SUBSCRIBE_FAILED,11,"failed to subscribe");
+    (INPUT_DESERIALIZE,12,"input deserialize failed");
+    (CONFIGURATION_INIT_FAILED,13,"configuration init failed");
 );
 
 //// tests
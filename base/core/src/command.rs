@@ -61,6 +61,8 @@ pub struct Command<'a> {
     pub fun: Option<Arc<ClosureFunc<'a>>>,
     pub meta_data: MetaData,
     pub run_type: RunType,
+    // module name of the extension which registered the command
+    pub owner: &'static str,
     seal: bool,
 }
 
@@ -95,8 +97,9 @@ impl<'a> Clone for Command<'a> {
         Command {
             protocol_id: self.protocol_id.clone(),
             fun: self.fun.clone(),
-            meta_data: Default::default(),
+            meta_data: self.meta_data.clone(),
             run_type: self.run_type,
+            owner: self.owner,
             seal: false,
         }
     }
@@ -106,6 +109,10 @@ pub struct MetaData {
     pub asy: bool,
     pub request_type: AliasRequestType,
     pub response_type: AliasResponseType,
+    pub description: Option<&'static str>,
+    // json schema documents, used to build the openapi output
+    pub request_schema: Option<&'static str>,
+    pub response_schema: Option<&'static str>,
 }
 
 impl Clone for MetaData {
@@ -114,6 +121,9 @@ impl Clone for MetaData {
             asy: self.asy,
             request_type: self.request_type,
             response_type: self.response_type,
+            description: self.description,
+            request_schema: self.request_schema,
+            response_schema: self.response_schema,
         }
     }
 }
@@ -124,6 +134,9 @@ impl Default for MetaData {
             asy: false,
             request_type: 0,
             response_type: 0,
+            description: None,
+            request_schema: None,
+            response_schema: None,
         }
     }
 }
@@ -149,6 +162,10 @@ impl<'a> Command<'a> {
         self.run_type = r;
         self
     }
+    pub fn with_owner(mut self, owner: &'static str) -> Self {
+        self.owner = owner;
+        self
+    }
     pub fn do_seal(mut self) -> Self {
         self.seal = true;
         self
@@ -169,6 +186,21 @@ impl MetaData {
         self.response_type = r;
         self
     }
+
+    pub fn with_description(mut self, d: &'static str) -> Self {
+        self.description = Some(d);
+        self
+    }
+
+    pub fn with_request_schema(mut self, s: &'static str) -> Self {
+        self.request_schema = Some(s);
+        self
+    }
+
+    pub fn with_response_schema(mut self, s: &'static str) -> Self {
+        self.response_schema = Some(s);
+        self
+    }
 }
 
 pub struct CommandContext<'a> {
@@ -191,6 +223,7 @@ impl<'a> Default for Command<'a> {
             fun: None,
             meta_data: Default::default(),
             run_type: 0,
+            owner: "",
             seal: false,
         }
     }
@@ -0,0 +1,375 @@
+// 配置接入 CellApplication: ConfigurationExtension 在 on_init 里初始化 Manager ,
+// 其他 extension 通过 SharedConfiguration 组件读取配置 ,热更新以 ConfigurationChangedEvent 的形式发布
+use crate::bus::{publish_application_events, EventBus};
+use crate::cerror::{CellError, CellResult, ErrorEnumsStruct};
+use crate::event::Event;
+use crate::extension::{ExtensionFactory, NodeContext, NodeExtension};
+use crate::module::ModuleEnumsStruct;
+use clap::Arg;
+use configuration::error::{ConfigurationError, ConfigurationResult};
+use configuration::logger::{reload_logger, watch_logger};
+use configuration::manager::Manager;
+use configuration::watch::{ConfigWatcher, WatchOptions};
+use core::any::Any;
+use core::cell::RefCell;
+use logsdk::common::LogLevel;
+use logsdk::module::CellModule;
+use serde::de::DeserializeOwned;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, RwLock};
+
+pub const CONFIG_ROOT: &'static str = "config-root";
+pub const CONFIG_TYPE: &'static str = "config-type";
+pub const CONFIG_SET: &'static str = "set";
+pub const CONFIG_WATCH: &'static str = "config-watch";
+pub const DEFAULT_CONFIG_ROOT: &'static str = "./config";
+
+// subscribe with `subscribe_application_events(bus, id, Some(vec![CONFIGURATION_CHANGED]))`
+pub const CONFIGURATION_CHANGED: &'static str = "configuration_changed";
+
+pub struct ConfigurationChangedEvent {
+    pub module: String,
+    pub value: Arc<serde_json::Value>,
+}
+
+impl ConfigurationChangedEvent {
+    pub fn new(module: &str, value: Arc<serde_json::Value>) -> Self {
+        Self {
+            module: String::from(module),
+            value,
+        }
+    }
+
+    pub fn get<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        T::deserialize(self.value.as_ref())
+    }
+}
+
+impl Display for ConfigurationChangedEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ConfigurationChangedEvent msg,module:{}", self.module)
+    }
+}
+
+impl Event for ConfigurationChangedEvent {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// the component other extensions pull with `#[component]` ,empty until ConfigurationExtension is inited
+#[derive(Clone, Default)]
+pub struct SharedConfiguration {
+    manager: Arc<RwLock<Option<Arc<Manager>>>>,
+}
+
+impl SharedConfiguration {
+    pub fn is_initialized(&self) -> bool {
+        self.manager.read().unwrap().is_some()
+    }
+
+    pub fn get_manager(&self) -> Option<Arc<Manager>> {
+        self.manager.read().unwrap().clone()
+    }
+
+    pub fn get_config<T: DeserializeOwned + Clone>(&self, module: &str) -> ConfigurationResult<T> {
+        match self.manager.read().unwrap().as_ref() {
+            Some(m) => m.get_configuration().get_config::<T>(module),
+            None => Err(ConfigurationError::StringError(String::from(
+                "configuration is not initialized ,read it after ConfigurationExtension::on_init",
+            ))),
+        }
+    }
+
+    fn set(&self, manager: Manager) {
+        *self.manager.write().unwrap() = Some(Arc::new(manager));
+    }
+}
+
+// `config_type` falls back to the `defaultType` of root.json
+pub fn load_manager(
+    root: &str,
+    config_type: Option<&str>,
+    sets: &[String],
+) -> ConfigurationResult<Manager> {
+    let mut m = Manager::prepare(root, config_type, sets)?;
+    m.initialize()?;
+    Ok(m)
+}
+
+pub struct ConfigurationExtensionFactory {}
+
+impl ExtensionFactory for ConfigurationExtensionFactory {
+    fn build_extension(
+        &self,
+        components: Vec<Arc<Box<dyn Any>>>,
+    ) -> Option<Arc<RefCell<dyn NodeExtension>>> {
+        let shared = components
+            .iter()
+            .find_map(|c| c.downcast_ref::<SharedConfiguration>())
+            .cloned()
+            .unwrap_or_default();
+        Some(Arc::new(RefCell::new(ConfigurationExtension::new(shared))))
+    }
+
+    fn components(&self) -> Option<Vec<Arc<Box<dyn Any>>>> {
+        let mut ret: Vec<Arc<Box<dyn Any>>> = Vec::new();
+        ret.push(Arc::new(Box::new(SharedConfiguration::default())));
+        Some(ret)
+    }
+}
+
+pub struct ConfigurationExtension {
+    shared: SharedConfiguration,
+    // only with --config-watch ,dropping it stops the reloads
+    watcher: Option<ConfigWatcher>,
+}
+
+impl ConfigurationExtension {
+    pub fn new(shared: SharedConfiguration) -> Self {
+        Self {
+            shared,
+            watcher: None,
+        }
+    }
+
+    fn watch(&mut self, m: &Manager, bus: EventBus<Box<dyn Event>>) -> ConfigurationResult<()> {
+        let watcher = m.watch(WatchOptions::default())?;
+        watch_logger(&watcher);
+        publish_configuration_changes(&watcher, Arc::new(bus));
+        self.watcher = Some(watcher);
+        Ok(())
+    }
+}
+
+impl NodeExtension for ConfigurationExtension {
+    fn module(&self) -> CellModule {
+        ModuleEnumsStruct::CONFIGURATION.clone()
+    }
+
+    fn get_options<'a>(&self) -> Option<Vec<Arg<'a>>> {
+        Some(vec![
+            Arg::new(CONFIG_ROOT)
+                .long(CONFIG_ROOT)
+                .help("the directory containing root.json")
+                .takes_value(true)
+                .default_value(DEFAULT_CONFIG_ROOT)
+                .required(false),
+            Arg::new(CONFIG_TYPE)
+                .long(CONFIG_TYPE)
+                .help("the config type to load ,defaults to the defaultType of root.json")
+                .takes_value(true)
+                .required(false),
+            Arg::new(CONFIG_SET)
+                .long(CONFIG_SET)
+                .help("override a value ,module.key=value")
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .required(false),
+            Arg::new(CONFIG_WATCH)
+                .long(CONFIG_WATCH)
+                .help("reload changed module files and publish them as ConfigurationChangedEvent")
+                .takes_value(false)
+                .required(false),
+        ])
+    }
+
+    fn on_init(&mut self, ctx: Arc<RefCell<NodeContext>>) -> CellResult<()> {
+        let matchers = ctx.borrow().get_matchers();
+        let root = matchers
+            .value_of(CONFIG_ROOT)
+            .unwrap_or(DEFAULT_CONFIG_ROOT);
+        let sets: Vec<String> = matchers
+            .values_of(CONFIG_SET)
+            .map(|v| v.map(String::from).collect())
+            .unwrap_or_default();
+        // logger 模块在其他 extension 初始化之前生效
+        let bus = ctx.borrow().bus.clone();
+        let loaded = load_manager(root, matchers.value_of(CONFIG_TYPE), sets.as_slice())
+            .and_then(|m| reload_logger(m.get_configuration()).map(|_| m))
+            .and_then(|m| {
+                if matchers.is_present(CONFIG_WATCH) {
+                    self.watch(&m, bus)?;
+                }
+                Ok(m)
+            });
+        match loaded {
+            Ok(m) => {
+                cinfo!(
+                    ModuleEnumsStruct::CONFIGURATION,
+                    "configuration loaded ,root:{} ,type:{}",
+                    root,
+                    m.config_type
+                );
+                self.shared.set(m);
+                Ok(())
+            }
+            Err(e) => {
+                cerror!(
+                    ModuleEnumsStruct::CONFIGURATION,
+                    "load configuration from {} failed:{}",
+                    root,
+                    e
+                );
+                Err(CellError::from(ErrorEnumsStruct::CONFIGURATION_INIT_FAILED)
+                    .with_error(Box::new(e)))
+            }
+        }
+    }
+
+    fn on_close(&mut self, _ctx: Arc<RefCell<NodeContext>>) -> CellResult<()> {
+        if let Some(w) = self.watcher.take() {
+            w.stop();
+        }
+        Ok(())
+    }
+}
+
+pub fn publish_configuration_changes(watcher: &ConfigWatcher, bus: Arc<EventBus<Box<dyn Event>>>) {
+    watcher.on_change(move |module, value| {
+        publish_application_events(
+            bus.clone(),
+            Box::new(ConfigurationChangedEvent::new(module, value)),
+            Some(vec![String::from(CONFIGURATION_CHANGED)]),
+        );
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bus::{subscribe_application_events, EventBus};
+    use crate::config::{
+        ConfigurationChangedEvent, ConfigurationExtensionFactory, SharedConfiguration,
+        CONFIGURATION_CHANGED,
+    };
+    use crate::event::Event;
+    use crate::extension::{ExtensionFactory, NodeContext, NodeExtension};
+    use clap::App;
+    use core::cell::RefCell;
+    use serde::Deserialize;
+    use std::fs;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+    use tokio::runtime::Runtime;
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Nacos {
+        server_addr: String,
+    }
+
+    #[test]
+    fn test_changed_event() {
+        let e = ConfigurationChangedEvent::new(
+            "nacos",
+            Arc::new(serde_json::json!({"serverAddr":"10.0.0.1"})),
+        );
+        assert_eq!(e.get::<Nacos>().unwrap().server_addr, "10.0.0.1");
+        assert_eq!(e.to_string(), "ConfigurationChangedEvent msg,module:nacos");
+    }
+
+    fn init_with(args: Vec<&str>) -> (SharedConfiguration, bool) {
+        let rt = Arc::new(Runtime::new().unwrap());
+        let (shared, _, ok) = init_on(EventBus::new(rt.clone()), rt, args);
+        (shared, ok)
+    }
+
+    fn init_on(
+        bus: EventBus<Box<dyn Event>>,
+        rt: Arc<Runtime>,
+        args: Vec<&str>,
+    ) -> (SharedConfiguration, Arc<RefCell<dyn NodeExtension>>, bool) {
+        let factory = ConfigurationExtensionFactory {};
+        let components = factory.components().unwrap();
+        let shared = components[0]
+            .downcast_ref::<SharedConfiguration>()
+            .unwrap()
+            .clone();
+        let ext = factory.build_extension(components).unwrap();
+        let matchers = App::new("test")
+            .args(ext.borrow().get_options().unwrap())
+            .get_matches_from(args);
+
+        let mut ctx = NodeContext::new(rt, bus);
+        ctx.set_matchers(matchers);
+        let ok = ext.borrow_mut().init(Arc::new(RefCell::new(ctx))).is_ok();
+        (shared, ext, ok)
+    }
+
+    #[test]
+    fn test_configuration_extension() {
+        let root = "../../sdk/configuration/config";
+        let (shared, ok) = init_with(vec![
+            "test",
+            "--config-root",
+            root,
+            "--config-type",
+            "test1",
+            "--set",
+            "nacos.namespace=dev",
+        ]);
+        assert!(ok);
+        let v = shared.get_config::<serde_json::Value>("nacos").unwrap();
+        assert_eq!(
+            v,
+            serde_json::json!({"serverAddr":"12345","namespace":"dev"})
+        );
+
+        // defaultType of root.json
+        let (shared, ok) = init_with(vec!["test", "--config-root", root]);
+        assert!(ok);
+        assert_eq!(shared.get_manager().unwrap().config_type, "Default");
+
+        let (shared, ok) = init_with(vec!["test", "--config-root", "./not_exists"]);
+        assert!(!ok);
+        assert!(!shared.is_initialized());
+        assert!(shared.get_config::<serde_json::Value>("nacos").is_err());
+    }
+
+    #[test]
+    fn test_config_watch() {
+        let dir = std::env::temp_dir().join(format!("core-config-watch-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("Default")).unwrap();
+        fs::write(
+            dir.join("root.json"),
+            r#"{"types":{"Default":{"parent":null}},"defaultType":"Default","configs":[{"modules":{"nacos":"nacos.json"},"schema":null}],"plugins":{}}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("Default/nacos.json"), r#"{"serverAddr":"a"}"#).unwrap();
+
+        let rt = Arc::new(Runtime::new().unwrap());
+        let bus = EventBus::new(rt.clone());
+        bus.clone().start();
+        let sub = subscribe_application_events(
+            bus.clone(),
+            "config_watch_test",
+            Some(vec![String::from(CONFIGURATION_CHANGED)]),
+        );
+        let root = dir.to_str().unwrap();
+        let (shared, ext, ok) = init_on(
+            bus,
+            rt,
+            vec!["test", "--config-root", root, "--config-watch"],
+        );
+        assert!(ok);
+        // 等 watcher 记下文件的初始状态
+        thread::sleep(Duration::from_millis(300));
+        fs::write(dir.join("Default/nacos.json"), r#"{"serverAddr":"b"}"#).unwrap();
+
+        let e = sub.recv_timeout(Duration::from_secs(10)).unwrap();
+        let e = e
+            .as_any()
+            .downcast_ref::<ConfigurationChangedEvent>()
+            .unwrap();
+        assert_eq!(e.module, "nacos");
+        assert_eq!(e.get::<Nacos>().unwrap().server_addr, "b");
+        // the shared manager sees the reload as well
+        let n = shared.get_config::<serde_json::Value>("nacos").unwrap();
+        assert_eq!(n, serde_json::json!({"serverAddr":"b"}));
+
+        drop(ext);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -20,4 +20,21 @@ impl ProtocolStatus {
     pub const SUCCESS: &'static EnumsProtocolStatus = &Status(1 << 0);
     pub const FAIL: &'static EnumsProtocolStatus = &Status(1 << 1);
     pub const TIMEOUT: &'static EnumsProtocolStatus = &Status(1 << 1 | 1 << 2);
+    pub const SERVICE_UNAVAILABLE: &'static EnumsProtocolStatus = &Status(1 << 1 | 1 << 3);
+}
+
+impl EnumsProtocolStatus {
+    // http status code of the protocol status, failures keep 200 and carry the error in the body
+    pub fn http_status(&self) -> u16 {
+        match self {
+            Status(v) if *v == ProtocolStatus::SERVICE_UNAVAILABLE.as_i64() => 503,
+            Status(v) if *v == ProtocolStatus::TIMEOUT.as_i64() => 504,
+            _ => 200,
+        }
+    }
+    pub const fn as_i64(&self) -> i64 {
+        match self {
+            Status(v) => *v,
+        }
+    }
 }
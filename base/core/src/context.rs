@@ -1,6 +1,8 @@
 use crate::cerror::CellResult;
 use crate::command::{Command, CommandContext};
+use crate::constants::ProtocolStatus;
 use crate::core::ProtocolID;
+use crate::metrics::record_request;
 use crate::request::{MockRequest, ServerRequestTrait, ServerResponseTrait};
 use crate::response::MockResponse;
 use crate::summary::{Summary, SummaryTrait};
@@ -87,7 +89,8 @@ impl<'a> Context for BaseBuzzContext<'a> {
 
 impl<'a> BaseBuzzContext<'a> {
     fn sync_response(&mut self, resp: ContextResponseWrapper<'a>) -> CellResult<()> {
-        let now = Local::now().timestamp();
+        // request_timestamp is in millis ,see the http dispatcher
+        let now = Local::now().timestamp_millis();
         let consume_time = now - self.request_timestamp;
         let sequence_id = self.command_context.summary.get_sequence_id();
         cinfo!(
@@ -100,8 +103,13 @@ impl<'a> BaseBuzzContext<'a> {
         );
 
         let mut mut_resp = resp.borrow();
-        // TODO status
-        let status = resp.status();
+        let status = resp.status().map_or(200, |v| v.http_status());
+        record_request(
+            self.command_context.summary.get_protocol_id(),
+            resp.status()
+                .map_or(ProtocolStatus::SUCCESS.as_i64(), |v| v.as_i64()),
+            consume_time.max(0) as f64 / 1000.0,
+        );
 
         // TODO , fired or not
 
@@ -146,7 +154,7 @@ impl<'a> BaseBuzzContext<'a> {
                     .server_response
                     .add_header(CONTENT_LENGTH, length_value);
                 let bbb = Body::from(body);
-                let fire_resp = Response::builder().body(bbb).unwrap();
+                let fire_resp = Response::builder().status(status).body(bbb).unwrap();
                 self.command_context
                     .server_response
                     .fire_result(fire_resp)?
@@ -4,6 +4,7 @@ use crate::command::{mock_context, Command, CommandContext};
 use crate::context::{BaseBuzzContext, BuzzContextTrait, ContextWrapper};
 use crate::core::{ExecutorValueTrait, ProtocolID};
 use crate::extension::NodeContext;
+use crate::metrics::record_selector_miss;
 use crate::module::ModuleEnumsStruct;
 use crate::request::{ServerRequestTrait, ServerResponseTrait};
 use crate::selector::{CommandSelector, SelectorRequest, SelectorStrategy};
@@ -81,8 +82,12 @@ impl<'e: 'a, 'a> DefaultDispatcher<'e, 'a> {
         if let Some(c) = cmd_res {
             cmd = c;
         } else {
-            cerror!(
+            record_selector_miss();
+            // 扫描类请求会刷屏,按调用点限流
+            cerror_limited!(
                 ModuleEnumsStruct::DISPATCHER,
+                10,
+                1000,
                 "command not exists,ip:{},protocol:{}",
                 req_rc.get_ip(),
                 req_rc.get_string_protocol()
@@ -150,6 +155,7 @@ mod tests {
     use http::header::HeaderName;
     use http::Response;
     use hyper::Body;
+    use logsdk::capture::capture;
     use logsdk::common::LogLevel;
     use logsdk::module;
     use logsdk::module::CellModule;
@@ -184,4 +190,27 @@ mod tests {
         let ctx = DispatchContext::new(req, resp);
         futures::executor::block_on(dispatcher.dispatch(ctx));
     }
+
+    #[test]
+    fn test_dispatch_missing_command() {
+        let (txx, rxx) = std::sync::mpsc::channel::<Response<Body>>();
+        let vec_executors: Vec<Box<dyn CommandSelector>> =
+            vec![Box::new(MockDefaultPureSelector::new())];
+        let selector = SelectorStrategy::new(vec_executors);
+        let dispatcher = DefaultDispatcher::new(
+            Box::new(mock_channel()),
+            selector,
+            Box::new(MockDispatcher {}),
+        );
+        let req = Box::new(MockRequest {
+            protocol: "missing",
+        });
+        let resp = Box::new(MockResponse::new(txx));
+
+        let logs = capture();
+        futures::executor::block_on(dispatcher.dispatch(DispatchContext::new(req, resp)));
+        logs.assert_logged("DISPATCHER", LogLevel::Error, "command not exists");
+        logs.assert_logged("DISPATCHER", LogLevel::Error, "protocol:missing");
+        assert!(rxx.try_recv().is_ok());
+    }
 }
@@ -10,6 +10,7 @@ use crate::banner::{BLESS, CLOSE, INIT, START};
 use crate::bus::{
     publish_application_events, subscribe_application_events, DefaultRegexQuery, EventBus,
 };
+use crate::catalogue::{dump_document, DUMP_COMMANDS, DUMP_OPENAPI};
 use crate::cerror::{CellError, CellResult, ErrorEnumsStruct};
 use crate::command::Command;
 use crate::core::{conv_protocol_to_string, RunType};
@@ -17,6 +18,8 @@ use crate::event::{
     ApplicationCloseEvent, ApplicationEnvironmentPreparedEvent, ApplicationInitEvent,
     ApplicationReadyEvent, ApplicationStartedEvent, CallBackEvent, Event, NextStepEvent,
 };
+use crate::health::{HealthCheck, HealthState, InternalHealthExtension};
+use crate::metrics::{record_phase, DEFAULT_REGISTRY, EVENT_BUS_QUEUE_DEPTH};
 use crate::module::ModuleEnumsStruct;
 use clap::{arg, command, App, Arg, ArgMatches};
 use crossbeam::channel::{bounded, Receiver, Select, Sender};
@@ -25,7 +28,7 @@ use flo_stream::{MessagePublisher, Publisher, Subscriber};
 use futures::future::ok;
 use futures::StreamExt;
 use logsdk::common::LogLevel;
-use logsdk::module::CellModule;
+use logsdk::module::{CellModule, Module};
 use shaku::{module, Component, HasComponent, Interface};
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
@@ -181,9 +184,40 @@ impl ExtensionManagerBuilder {
 
         let subsc = subscribe_application_events(clone_bus.clone(), extension_manager, None);
 
+        let depth_bus = clone_bus.clone();
+        let depth_sub = subsc.clone();
+        DEFAULT_REGISTRY.register_gauge_fn(
+            EVENT_BUS_QUEUE_DEPTH,
+            "events waiting to be consumed",
+            Arc::new(move || {
+                vec![
+                    (
+                        vec![("queue", String::from("bus"))],
+                        depth_bus.queue_depth() as f64,
+                    ),
+                    (
+                        vec![("queue", String::from(extension_manager))],
+                        depth_sub.len() as f64,
+                    ),
+                ]
+            }),
+        );
+
         // internal
         let mut inter_tokio = InternalTokioExtension::new();
         self.extensions.push(Arc::new(RefCell::new(inter_tokio)));
+        let inter_health = InternalHealthExtension::new(ctx.health.clone());
+        self.extensions.push(Arc::new(RefCell::new(inter_health)));
+        // 其它 extension 在 on_init 里读配置 ,只把 configuration 提到最前 ,其余保持注册顺序
+        let configuration = self.extensions.iter().position(|e| {
+            let m = e.borrow().module();
+            m.index() == ModuleEnumsStruct::CONFIGURATION.index()
+                && m.name() == ModuleEnumsStruct::CONFIGURATION.name()
+        });
+        if let Some(i) = configuration {
+            let e = self.extensions.remove(i);
+            self.extensions.insert(0, e);
+        }
 
         ExtensionManager {
             extension: self.extensions,
@@ -246,9 +280,10 @@ impl ExtensionManager {
         let mut commands: Vec<Command> = Vec::new();
         while i < self.extension.len() {
             let v = self.extension.get_mut(i).unwrap();
+            let owner = v.clone().borrow().module().get_name();
             if let Some(vecc) = v.clone().borrow_mut().commands() {
                 for c in vecc {
-                    commands.push(c.clone());
+                    commands.push(c.clone().with_owner(owner));
                 }
             }
             i += 1;
@@ -257,6 +292,18 @@ impl ExtensionManager {
         self.ctx.clone().borrow_mut().set_commands(commands.clone());
     }
 
+    fn init_health_checks(&mut self) {
+        let health = self.ctx.clone().borrow().health.clone();
+        for v in &self.extension {
+            let owner = v.clone().borrow().module().get_name();
+            if let Some(checks) = v.clone().borrow_mut().health_checks() {
+                for c in checks {
+                    health.add_check(owner, c);
+                }
+            }
+        }
+    }
+
     // fn fill_ctx(&mut self) {
     //     let mut cmds = Vec::new();
     //     for c in &self.commands {
@@ -343,6 +390,7 @@ impl ExtensionManager {
             match actual {
                 Some(v) => {
                     self.init_commands();
+                    self.init_health_checks();
                     res = self.on_start();
                     // notify
                     publish_application_events(
@@ -360,6 +408,11 @@ impl ExtensionManager {
             match actual {
                 Some(v) => {
                     res = self.on_ready();
+                    publish_application_events(
+                        self.bus.clone(),
+                        Box::new(NextStepEvent::new(self.step)),
+                        None,
+                    );
                 }
                 None => {}
             }
@@ -369,22 +422,51 @@ impl ExtensionManager {
             let actual = any.downcast_ref::<ApplicationCloseEvent>();
             match actual {
                 Some(v) => {
+                    // probes report not-ready as soon as the shutdown begins
+                    self.ctx.clone().borrow().health.mark_closing();
                     res = self.on_close();
+                    // 关闭阶段的日志都要落盘
+                    logsdk::writer::flush();
+                    publish_application_events(
+                        self.bus.clone(),
+                        Box::new(NextStepEvent::new(self.step)),
+                        None,
+                    );
                 }
                 None => {}
             }
         }
 
+        self.ctx.clone().borrow().health.set_step(self.step);
         res
     }
 
     pub fn on_prepare(&mut self, args: Vec<String>) -> CellResult<()> {
         self.verify_step(step_0)?;
         self.init_command_line(args)?;
+        self.dump_catalogue();
         self.step = step_0;
         Ok(())
     }
 
+    // --dump-commands / --dump-openapi print and exit here ,before any extension is initialized or started
+    fn dump_catalogue(&mut self) {
+        let matchers = self.ctx.clone().borrow().get_matchers();
+        let requested = [DUMP_COMMANDS, DUMP_OPENAPI]
+            .iter()
+            .any(|o| self.long_ops.contains(*o) && matchers.is_present(o));
+        if !requested {
+            return;
+        }
+        self.init_commands();
+        let doc = dump_document(&matchers, &self.ctx.clone().borrow().commands);
+        if let Some(v) = doc {
+            println!("{}", serde_json::to_string_pretty(&v).unwrap());
+            logsdk::writer::shutdown_async_writer();
+            std::process::exit(0);
+        }
+    }
+
     pub fn on_init(&mut self) -> CellResult<()> {
         self.verify_step(step_1)?;
 
@@ -414,6 +496,11 @@ impl ExtensionManager {
                 e.clone().borrow_mut().module().get_name(),
                 wh.elapsed().as_secs()
             );
+            record_phase(
+                e.clone().borrow_mut().module().get_name(),
+                "init",
+                wh.elapsed().as_secs_f64(),
+            );
             i += 1;
         }
         self.step = step_1;
@@ -448,6 +535,11 @@ impl ExtensionManager {
                 e.clone().borrow_mut().module().get_name(),
                 wh.elapsed().as_secs()
             );
+            record_phase(
+                e.clone().borrow_mut().module().get_name(),
+                "start",
+                wh.elapsed().as_secs_f64(),
+            );
             i += 1;
         }
         self.step = step_2;
@@ -484,6 +576,11 @@ impl ExtensionManager {
                 e.clone().borrow_mut().module().get_name(),
                 wh.elapsed().as_secs()
             );
+            record_phase(
+                e.clone().borrow_mut().module().get_name(),
+                "ready",
+                wh.elapsed().as_secs_f64(),
+            );
             i += 1;
         }
         self.step = step_3;
@@ -518,6 +615,11 @@ impl ExtensionManager {
                 e.clone().borrow_mut().module().get_name(),
                 wh.elapsed().as_secs()
             );
+            record_phase(
+                e.clone().borrow_mut().module().get_name(),
+                "close",
+                wh.elapsed().as_secs_f64(),
+            );
             i += 1;
         }
         self.step = step_4;
@@ -551,6 +653,7 @@ pub struct NodeContext {
     pub commands: HashMap<String, Command<'static>>,
 
     pub bus: EventBus<Box<dyn Event>>,
+    pub health: Arc<HealthState>,
 }
 
 impl NodeContext {
@@ -560,6 +663,7 @@ impl NodeContext {
             matchers: ArgMatches::default(),
             commands: HashMap::new(),
             bus: bus,
+            health: Arc::new(HealthState::default()),
         }
     }
 
@@ -642,6 +746,10 @@ pub trait NodeExtension {
     fn commands(&mut self) -> Option<Vec<Command<'static>>> {
         None
     }
+    // checks contributed to /health/ready, keyed by the module name
+    fn health_checks(&mut self) -> Option<Vec<HealthCheck>> {
+        None
+    }
     // fn resolve(&mut self, any: Arc<Box<dyn Any>>) {}
 }
 
@@ -689,6 +797,7 @@ mod tests {
     use crate::bus::{
         publish_application_events, subscribe_application_events, DefaultRegexQuery, EventBus,
     };
+    use crate::config::{ConfigurationExtension, SharedConfiguration};
     use crate::event::{
         ApplicationCloseEvent, ApplicationEnvironmentPreparedEvent, ApplicationInitEvent,
         ApplicationReadyEvent, ApplicationStartedEvent, CallBackEvent, Event, NextStepEvent,
@@ -702,7 +811,7 @@ mod tests {
     use flo_stream::{MessagePublisher, Publisher, Subscriber};
     use futures::StreamExt;
     use logsdk::common::LogLevel;
-    use logsdk::module::CellModule;
+    use logsdk::module::{CellModule, Module};
     use std::borrow::Borrow;
     use std::cell::{Cell, RefCell};
     use std::collections::{HashMap, HashSet};
@@ -718,6 +827,40 @@ mod tests {
     #[test]
     fn test_extension() {}
 
+    struct NamedExtension {
+        name: &'static str,
+    }
+
+    impl NodeExtension for NamedExtension {
+        fn module(&self) -> CellModule {
+            CellModule::new(1, self.name, &LogLevel::Info)
+        }
+    }
+
+    #[test]
+    fn test_configuration_first() {
+        let runtime = Arc::new(tokio::runtime::Builder::new_multi_thread().build().unwrap());
+        let (_tx, rx) = mpsc::channel::<u8>(1);
+        let bus = EventBus::<Box<dyn Event>>::new(runtime.clone());
+        let m = ExtensionManagerBuilder::default()
+            .with_tokio(runtime.clone())
+            .with_close_notifyc(rx)
+            .with_bus(bus)
+            .with_extension(Arc::new(RefCell::new(NamedExtension { name: "A" })))
+            .with_extension(Arc::new(RefCell::new(ConfigurationExtension::new(
+                SharedConfiguration::default(),
+            ))))
+            .with_extension(Arc::new(RefCell::new(NamedExtension { name: "B" })))
+            .build();
+        let names: Vec<&'static str> = m
+            .extension
+            .iter()
+            .map(|e| e.as_ref().borrow().module().name())
+            .collect();
+        // registration order is kept for everything but the configuration extension
+        assert_eq!(names[..3], ["CONFIGURATION", "A", "B"]);
+    }
+
     fn create_builder() -> (
         ExtensionManager,
         Arc<Runtime>,
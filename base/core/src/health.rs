@@ -0,0 +1,266 @@
+// 健康检查: /health/live /health/ready ,ready 依赖生命周期阶段以及各个 extension 的检查结果
+use crate::cell_macro::fire_result;
+use crate::command::{ClosureFunc, Command};
+use crate::constants::ProtocolStatus;
+use crate::core::runTypeHttpGet;
+use crate::extension::{step_3, NodeExtension};
+use crate::module::ModuleEnumsStruct;
+use crate::output::{as_json_bytes, Serializable};
+use crate::wrapper::ContextResponseWrapper;
+use logsdk::common::LogLevel;
+use logsdk::module::CellModule;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, RwLock};
+
+pub const LIVE_PROTOCOL: &'static str = "/health/live";
+pub const READY_PROTOCOL: &'static str = "/health/ready";
+
+pub type HealthCheck = Arc<dyn Fn() -> HealthIndicator + Send + Sync>;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HealthIndicator {
+    pub up: bool,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub details: BTreeMap<String, String>,
+}
+
+impl HealthIndicator {
+    pub fn up() -> Self {
+        HealthIndicator {
+            up: true,
+            details: BTreeMap::new(),
+        }
+    }
+    pub fn down(reason: &str) -> Self {
+        HealthIndicator {
+            up: false,
+            details: BTreeMap::new(),
+        }
+        .with_detail("reason", reason)
+    }
+    pub fn with_detail(mut self, key: &str, value: &str) -> Self {
+        self.details.insert(String::from(key), String::from(value));
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HealthReport {
+    pub up: bool,
+    pub step: u8,
+    pub closing: bool,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub checks: BTreeMap<String, HealthIndicator>,
+}
+
+impl<'a> Serializable<'a> for HealthReport {}
+
+// shared by the extension manager (writes the step) and the probes (read it)
+pub struct HealthState {
+    step: AtomicU8,
+    closing: AtomicBool,
+    checks: RwLock<Vec<(&'static str, HealthCheck)>>,
+}
+
+impl Default for HealthState {
+    fn default() -> Self {
+        HealthState {
+            step: AtomicU8::new(0),
+            closing: AtomicBool::new(false),
+            checks: RwLock::new(Vec::new()),
+        }
+    }
+}
+
+impl HealthState {
+    pub fn set_step(&self, step: u8) {
+        self.step.store(step, Ordering::SeqCst)
+    }
+    pub fn get_step(&self) -> u8 {
+        self.step.load(Ordering::SeqCst)
+    }
+    pub fn mark_closing(&self) {
+        self.closing.store(true, Ordering::SeqCst)
+    }
+    pub fn is_closing(&self) -> bool {
+        self.closing.load(Ordering::SeqCst)
+    }
+    pub fn add_check(&self, owner: &'static str, check: HealthCheck) {
+        self.checks.write().unwrap().push((owner, check))
+    }
+
+    pub fn liveness(&self) -> HealthReport {
+        HealthReport {
+            up: true,
+            step: self.get_step(),
+            closing: self.is_closing(),
+            checks: BTreeMap::new(),
+        }
+    }
+
+    pub fn readiness(&self) -> HealthReport {
+        let step = self.get_step();
+        let closing = self.is_closing();
+        let mut up = step == step_3 && !closing;
+        let mut checks = BTreeMap::new();
+        // 同一个 extension 的第二个检查起 key 为 `owner#1` ,`owner#2` ...
+        let mut per_owner: BTreeMap<&str, usize> = BTreeMap::new();
+        for (owner, check) in self.checks.read().unwrap().iter() {
+            let indicator = check();
+            up = up && indicator.up;
+            let index = per_owner.entry(*owner).or_insert(0);
+            let key = match *index {
+                0 => String::from(*owner),
+                i => format!("{}#{}", owner, i),
+            };
+            *index += 1;
+            checks.insert(key, indicator);
+        }
+        HealthReport {
+            up,
+            step,
+            closing,
+            checks,
+        }
+    }
+}
+
+fn probe_command(
+    protocol: &'static str,
+    state: Arc<HealthState>,
+    probe: fn(&HealthState) -> HealthReport,
+) -> Command<'static> {
+    let f = ClosureFunc::new(Arc::new(move |ctx, _| {
+        let report = probe(&state);
+        if report.up {
+            return fire_result(ctx, Ok(report));
+        }
+        // 非 ready 时以 503 返回,方便 k8s 之类的探针识别
+        match as_json_bytes(report) {
+            Ok(body) => {
+                let resp = ContextResponseWrapper::default()
+                    .with_status(ProtocolStatus::SERVICE_UNAVAILABLE)
+                    .with_body(body);
+                if let Err(e) = ctx.response(resp) {
+                    cerror!(ModuleEnumsStruct::HEALTH, "response failed:{}", e);
+                }
+            }
+            Err(e) => {
+                cerror!(
+                    ModuleEnumsStruct::HEALTH,
+                    "serialize health report failed:{}",
+                    e
+                );
+            }
+        }
+    }));
+    Command::default()
+        .with_protocol_id(protocol)
+        .with_run_type(runTypeHttpGet)
+        .with_executor(Arc::new(f))
+}
+
+////////////// internal
+pub struct InternalHealthExtension {
+    state: Arc<HealthState>,
+}
+
+impl InternalHealthExtension {
+    pub fn new(state: Arc<HealthState>) -> Self {
+        Self { state }
+    }
+}
+
+impl NodeExtension for InternalHealthExtension {
+    fn module(&self) -> CellModule {
+        ModuleEnumsStruct::HEALTH.clone()
+    }
+
+    fn required(&self) -> bool {
+        false
+    }
+
+    fn commands(&mut self) -> Option<Vec<Command<'static>>> {
+        Some(vec![
+            probe_command(LIVE_PROTOCOL, self.state.clone(), HealthState::liveness),
+            probe_command(READY_PROTOCOL, self.state.clone(), HealthState::readiness),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::{mock_context, CommandTrait};
+    use crate::extension::{step_2, step_3, NodeExtension};
+    use crate::health::{HealthIndicator, HealthState, InternalHealthExtension};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_readiness() {
+        let state = HealthState::default();
+        state.set_step(step_2);
+        assert!(!state.readiness().up);
+        assert!(state.liveness().up);
+
+        state.set_step(step_3);
+        assert!(state.readiness().up);
+
+        let db_up = Arc::new(AtomicBool::new(true));
+        let flag = db_up.clone();
+        state.add_check(
+            "DB",
+            Arc::new(move || {
+                if flag.load(Ordering::SeqCst) {
+                    HealthIndicator::up().with_detail("pool", "8")
+                } else {
+                    HealthIndicator::down("connection refused")
+                }
+            }),
+        );
+        let report = state.readiness();
+        assert!(report.up);
+        assert_eq!(report.checks["DB"].details["pool"], "8");
+
+        db_up.store(false, Ordering::SeqCst);
+        assert!(!state.readiness().up);
+
+        db_up.store(true, Ordering::SeqCst);
+        state.mark_closing();
+        assert!(!state.readiness().up);
+        assert!(state.liveness().up);
+    }
+
+    #[test]
+    fn test_readiness_checks_of_one_extension() {
+        let state = HealthState::default();
+        state.set_step(step_3);
+        state.add_check("DB", Arc::new(|| HealthIndicator::up()));
+        state.add_check("DB", Arc::new(|| HealthIndicator::down("replica lag")));
+        state.add_check("CACHE", Arc::new(|| HealthIndicator::up()));
+        let report = state.readiness();
+        assert!(!report.up);
+        assert_eq!(report.checks.len(), 3);
+        assert!(report.checks["DB"].up);
+        assert_eq!(report.checks["DB#1"].details["reason"], "replica lag");
+        assert!(report.checks["CACHE"].up);
+    }
+
+    #[test]
+    fn test_probe_commands() {
+        let state = Arc::new(HealthState::default());
+        let mut ext = InternalHealthExtension::new(state.clone());
+        let cmds = ext.commands().unwrap();
+
+        let (_, rxx, mut ctx) = mock_context();
+        cmds[1].execute(&mut ctx);
+        assert_eq!(rxx.recv().unwrap().status(), 503);
+
+        state.set_step(step_3);
+        let (_, rxx, mut ctx) = mock_context();
+        cmds[1].execute(&mut ctx);
+        assert_eq!(rxx.recv().unwrap().status(), 200);
+    }
+}
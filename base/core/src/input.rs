@@ -1 +1,52 @@
+use crate::cerror::{CellError, CellResult, ErrorEnumsStruct};
+use crate::request::ServerRequestTrait;
+use serde::de::DeserializeOwned;
+
 pub trait InputArchive {}
+
+pub fn from_json_bytes<T: DeserializeOwned>(data: &[u8]) -> CellResult<T> {
+    serde_json::from_slice::<T>(data)
+        .map_err(|e| CellError::from(ErrorEnumsStruct::INPUT_DESERIALIZE).with_error(Box::new(e)))
+}
+
+pub fn from_query<T: DeserializeOwned>(query: &str) -> CellResult<T> {
+    serde_urlencoded::from_str::<T>(query)
+        .map_err(|e| CellError::from(ErrorEnumsStruct::INPUT_DESERIALIZE).with_error(Box::new(e)))
+}
+
+// a non-empty body is decoded as json, otherwise the query string is used
+pub fn from_request<T: DeserializeOwned>(req: &dyn ServerRequestTrait) -> CellResult<T> {
+    if let Some(body) = req.get_body() {
+        if !body.is_empty() {
+            return from_json_bytes(body.as_ref());
+        }
+    }
+    let query = req.get_query().unwrap_or_default();
+    from_query(query.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input::{from_json_bytes, from_query};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug)]
+    pub struct DemoInput {
+        name: String,
+        age: u32,
+    }
+
+    #[test]
+    fn test_from_query() {
+        let v = from_query::<DemoInput>("name=charlie&age=12").unwrap();
+        assert_eq!(v.name, "charlie");
+        assert_eq!(v.age, 12);
+        assert!(from_query::<DemoInput>("name=charlie").is_err());
+    }
+
+    #[test]
+    fn test_from_json_bytes() {
+        let v = from_json_bytes::<DemoInput>(br#"{"name":"charlie","age":12}"#).unwrap();
+        assert_eq!(v.age, 12);
+    }
+}
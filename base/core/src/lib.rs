@@ -1,17 +1,23 @@
 #[macro_use]
 extern crate logsdk;
+#[macro_use]
+extern crate lazy_static;
 extern crate core as std_core;
+// lets the paths generated by cell-macro (`::cell_core::...`) resolve inside this crate too
+extern crate self as cell_core;
 
 pub mod application;
 mod banner;
 pub mod body;
 pub mod bus;
 mod byte_str;
+pub mod catalogue;
 pub mod cell_macro;
 pub mod cerror;
 pub mod channel;
 pub mod collector;
 pub mod command;
+pub mod config;
 pub mod constants;
 pub mod context;
 pub mod decorator;
@@ -20,7 +26,9 @@ pub mod dispatcher;
 pub mod event;
 pub mod extension;
 pub mod header;
+pub mod health;
 pub mod input;
+pub mod metrics;
 pub mod module;
 pub mod output;
 pub mod reactor;
@@ -0,0 +1,351 @@
+// 指标: counter / histogram / gauge ,以 prometheus text 格式导出
+use crate::command::{ClosureFunc, Command};
+use crate::constants::ProtocolStatus;
+use crate::core::runTypeHttpGet;
+use crate::extension::{ExtensionFactory, NodeExtension};
+use crate::module::ModuleEnumsStruct;
+use crate::wrapper::ContextResponseWrapper;
+use bytes::Bytes;
+use logsdk::common::LogLevel;
+use logsdk::module::CellModule;
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+pub const METRICS_PROTOCOL: &'static str = "/metrics";
+
+pub const REQUESTS_TOTAL: &'static str = "cell_requests_total";
+pub const REQUEST_DURATION: &'static str = "cell_request_duration_seconds";
+pub const SELECTOR_MISS_TOTAL: &'static str = "cell_selector_miss_total";
+pub const EVENT_BUS_QUEUE_DEPTH: &'static str = "cell_event_bus_queue_depth";
+pub const EXTENSION_PHASE_DURATION: &'static str = "cell_extension_phase_duration_seconds";
+
+pub const DEFAULT_BUCKETS: &'static [f64] = &[
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+pub type Labels = Vec<(&'static str, String)>;
+
+pub type GaugeFn = Arc<dyn Fn() -> Vec<(Labels, f64)> + Send + Sync>;
+
+lazy_static! {
+    pub static ref DEFAULT_REGISTRY: Registry = Registry::default();
+}
+
+struct Family<T> {
+    help: &'static str,
+    values: BTreeMap<Labels, T>,
+}
+
+impl<T> Family<T> {
+    fn new(help: &'static str) -> Self {
+        Family {
+            help,
+            values: BTreeMap::new(),
+        }
+    }
+}
+
+struct Histogram {
+    buckets: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(buckets: &'static [f64]) -> Self {
+        Histogram {
+            buckets,
+            counts: vec![0; buckets.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+    fn observe(&mut self, v: f64) {
+        for (i, b) in self.buckets.iter().enumerate() {
+            if v <= *b {
+                self.counts[i] += 1;
+            }
+        }
+        self.sum += v;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+pub struct Registry {
+    counters: Mutex<BTreeMap<&'static str, Family<u64>>>,
+    gauges: Mutex<BTreeMap<&'static str, Family<f64>>>,
+    gauge_fns: Mutex<BTreeMap<&'static str, (&'static str, GaugeFn)>>,
+    histograms: Mutex<BTreeMap<&'static str, Family<Histogram>>>,
+}
+
+impl Registry {
+    pub fn inc_counter(&self, name: &'static str, help: &'static str, labels: Labels) {
+        self.add_counter(name, help, labels, 1)
+    }
+
+    pub fn add_counter(&self, name: &'static str, help: &'static str, labels: Labels, v: u64) {
+        let mut counters = self.counters.lock().unwrap();
+        let family = counters.entry(name).or_insert_with(|| Family::new(help));
+        *family.values.entry(labels).or_insert(0) += v;
+    }
+
+    pub fn set_gauge(&self, name: &'static str, help: &'static str, labels: Labels, v: f64) {
+        let mut gauges = self.gauges.lock().unwrap();
+        let family = gauges.entry(name).or_insert_with(|| Family::new(help));
+        family.values.insert(labels, v);
+    }
+
+    // evaluated on every scrape ,for values owned by someone else (queue lengths etc.)
+    pub fn register_gauge_fn(&self, name: &'static str, help: &'static str, f: GaugeFn) {
+        self.gauge_fns.lock().unwrap().insert(name, (help, f));
+    }
+
+    pub fn observe(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        buckets: &'static [f64],
+        labels: Labels,
+        v: f64,
+    ) {
+        let mut histograms = self.histograms.lock().unwrap();
+        let family = histograms.entry(name).or_insert_with(|| Family::new(help));
+        family
+            .values
+            .entry(labels)
+            .or_insert_with(|| Histogram::new(buckets))
+            .observe(v);
+    }
+
+    pub fn get_counter(&self, name: &'static str, labels: &Labels) -> u64 {
+        self.counters
+            .lock()
+            .unwrap()
+            .get(name)
+            .and_then(|f| f.values.get(labels).cloned())
+            .unwrap_or(0)
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (name, family) in self.counters.lock().unwrap().iter() {
+            write_header(&mut out, name, family.help, "counter");
+            for (labels, v) in family.values.iter() {
+                let _ = writeln!(out, "{}{} {}", name, format_labels(labels, None), v);
+            }
+        }
+        for (name, family) in self.gauges.lock().unwrap().iter() {
+            write_header(&mut out, name, family.help, "gauge");
+            for (labels, v) in family.values.iter() {
+                let _ = writeln!(out, "{}{} {}", name, format_labels(labels, None), v);
+            }
+        }
+        // gauge fns are cloned out first ,so a slow callback does not hold the lock
+        let gauge_fns: Vec<(&'static str, &'static str, GaugeFn)> = self
+            .gauge_fns
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, (help, f))| (*name, *help, f.clone()))
+            .collect();
+        for (name, help, f) in gauge_fns {
+            write_header(&mut out, name, help, "gauge");
+            for (labels, v) in f() {
+                let _ = writeln!(out, "{}{} {}", name, format_labels(&labels, None), v);
+            }
+        }
+        for (name, family) in self.histograms.lock().unwrap().iter() {
+            write_header(&mut out, name, family.help, "histogram");
+            for (labels, h) in family.values.iter() {
+                for (i, b) in h.buckets.iter().enumerate() {
+                    let le = b.to_string();
+                    let _ = writeln!(
+                        out,
+                        "{}_bucket{} {}",
+                        name,
+                        format_labels(labels, Some(le.as_str())),
+                        h.counts[i]
+                    );
+                }
+                let _ = writeln!(
+                    out,
+                    "{}_bucket{} {}",
+                    name,
+                    format_labels(labels, Some("+Inf")),
+                    h.count
+                );
+                let _ = writeln!(out, "{}_sum{} {}", name, format_labels(labels, None), h.sum);
+                let _ = writeln!(
+                    out,
+                    "{}_count{} {}",
+                    name,
+                    format_labels(labels, None),
+                    h.count
+                );
+            }
+        }
+        out
+    }
+}
+
+fn write_header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn format_labels(labels: &Labels, le: Option<&str>) -> String {
+    let mut parts: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, escape_label(v)))
+        .collect();
+    if let Some(le) = le {
+        parts.push(format!("le=\"{}\"", le));
+    }
+    if parts.is_empty() {
+        return String::new();
+    }
+    format!("{{{}}}", parts.join(","))
+}
+
+fn escape_label(v: &str) -> String {
+    v.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+pub fn status_label(status: i64) -> String {
+    if status == ProtocolStatus::SUCCESS.as_i64() {
+        String::from("success")
+    } else if status == ProtocolStatus::TIMEOUT.as_i64() {
+        String::from("timeout")
+    } else if status == ProtocolStatus::SERVICE_UNAVAILABLE.as_i64() {
+        String::from("unavailable")
+    } else {
+        String::from("fail")
+    }
+}
+
+pub fn record_request(protocol: &str, status: i64, cost_seconds: f64) {
+    let labels = vec![
+        ("protocol", String::from(protocol)),
+        ("status", status_label(status)),
+    ];
+    DEFAULT_REGISTRY.inc_counter(REQUESTS_TOTAL, "requests handled", labels.clone());
+    DEFAULT_REGISTRY.observe(
+        REQUEST_DURATION,
+        "request latency",
+        DEFAULT_BUCKETS,
+        labels,
+        cost_seconds,
+    );
+}
+
+// 不带 protocol label ,未注册的 protocol 是任意输入,会导致基数膨胀
+pub fn record_selector_miss() {
+    DEFAULT_REGISTRY.inc_counter(
+        SELECTOR_MISS_TOTAL,
+        "requests without a matching command",
+        Vec::new(),
+    );
+}
+
+pub fn record_phase(extension: &'static str, phase: &'static str, cost_seconds: f64) {
+    DEFAULT_REGISTRY.set_gauge(
+        EXTENSION_PHASE_DURATION,
+        "duration of the extension lifecycle phases",
+        vec![
+            ("extension", String::from(extension)),
+            ("phase", String::from(phase)),
+        ],
+        cost_seconds,
+    );
+}
+
+pub struct MetricsExtensionFactory {}
+
+impl ExtensionFactory for MetricsExtensionFactory {
+    fn build_extension(
+        &self,
+        _components: Vec<Arc<Box<dyn Any>>>,
+    ) -> Option<Arc<RefCell<dyn NodeExtension>>> {
+        Some(Arc::new(RefCell::new(MetricsExtension {})))
+    }
+}
+
+pub struct MetricsExtension {}
+
+impl NodeExtension for MetricsExtension {
+    fn module(&self) -> CellModule {
+        ModuleEnumsStruct::METRICS.clone()
+    }
+
+    fn required(&self) -> bool {
+        false
+    }
+
+    fn commands(&mut self) -> Option<Vec<Command<'static>>> {
+        let f = ClosureFunc::new(Arc::new(|ctx, _| {
+            let resp = ContextResponseWrapper::default()
+                .with_status(ProtocolStatus::SUCCESS)
+                .with_header("content-type", "text/plain; version=0.0.4")
+                .with_body(Bytes::from(DEFAULT_REGISTRY.render()));
+            if let Err(e) = ctx.response(resp) {
+                cerror!(ModuleEnumsStruct::METRICS, "response failed:{}", e);
+            }
+        }));
+        Some(vec![Command::default()
+            .with_protocol_id(METRICS_PROTOCOL)
+            .with_run_type(runTypeHttpGet)
+            .with_executor(Arc::new(f))])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::{mock_context, CommandTrait};
+    use crate::extension::NodeExtension;
+    use crate::metrics::{MetricsExtension, Registry, DEFAULT_BUCKETS};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_render() {
+        let r = Registry::default();
+        let labels = vec![
+            ("protocol", String::from("/demo")),
+            ("status", String::from("success")),
+        ];
+        r.inc_counter("requests_total", "requests", labels.clone());
+        r.inc_counter("requests_total", "requests", labels.clone());
+        r.observe("latency", "latency", DEFAULT_BUCKETS, labels.clone(), 0.02);
+        r.register_gauge_fn(
+            "depth",
+            "queue depth",
+            Arc::new(|| vec![(vec![("queue", String::from("commands"))], 3.0)]),
+        );
+        assert_eq!(r.get_counter("requests_total", &labels), 2);
+
+        let out = r.render();
+        assert!(out.contains("# TYPE requests_total counter"));
+        assert!(out.contains(r#"requests_total{protocol="/demo",status="success"} 2"#));
+        assert!(out.contains(r#"latency_bucket{protocol="/demo",status="success",le="0.01"} 0"#));
+        assert!(out.contains(r#"latency_bucket{protocol="/demo",status="success",le="0.025"} 1"#));
+        assert!(out.contains(r#"latency_bucket{protocol="/demo",status="success",le="+Inf"} 1"#));
+        assert!(out.contains(r#"latency_count{protocol="/demo",status="success"} 1"#));
+        assert!(out.contains(r#"depth{queue="commands"} 3"#));
+    }
+
+    #[test]
+    fn test_metrics_command() {
+        let cmds = MetricsExtension {}.commands().unwrap();
+        let (_, rxx, mut ctx) = mock_context();
+        cmds[0].execute(&mut ctx);
+        let resp = rxx.recv().unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers()["content-type"], "text/plain; version=0.0.4");
+    }
+}
@@ -16,4 +16,9 @@ module_enums!(
     (INTERNAL_TOKIO,2,&logsdk::common::LogLevel::Info);
     (CELL_APPLICATION,3,&logsdk::common::LogLevel::Info);
     (DISPATCHER,4,&logsdk::common::LogLevel::Info);
+    (COMMAND,5,&logsdk::common::LogLevel::Info);
+    (CATALOGUE,6,&logsdk::common::LogLevel::Info);
+    (HEALTH,7,&logsdk::common::LogLevel::Info);
+    (METRICS,8,&logsdk::common::LogLevel::Info);
+    (CONFIGURATION,9,&logsdk::common::LogLevel::Info);
 );
@@ -2,6 +2,7 @@ use crate::cerror::CellResult;
 use crate::core::ProtocolID;
 use crate::header::name::CellHeaderName;
 use crate::header::value::CellHeaderValue;
+use bytes::Bytes;
 use http::header::HeaderName;
 use http::{HeaderValue, Response};
 use hyper::Body;
@@ -13,6 +14,12 @@ pub trait ServerRequestTrait: Send + Sync {
     fn as_any(&self) -> &dyn Any;
     fn get_string_protocol(&self) -> String;
     fn get_ip(&self) -> String;
+    fn get_body(&self) -> Option<Bytes> {
+        None
+    }
+    fn get_query(&self) -> Option<String> {
+        None
+    }
 }
 
 pub trait ServerResponseTrait: Send + Sync {
@@ -2,7 +2,7 @@ use crate::cerror::{CellError, CellResult, ErrorEnumsStruct};
 use crate::request::ServerResponseTrait;
 use futures::*;
 use http::header::HeaderName;
-use http::{HeaderValue, Response};
+use http::{HeaderMap, HeaderValue, Response};
 use hyper::Body;
 use rocket::figment::map;
 use serde::ser::Error;
@@ -31,11 +31,15 @@ use std::sync::mpsc::Sender;
 
 pub struct MockResponse {
     tx: Sender<Response<Body>>,
+    headers: HeaderMap,
 }
 
 impl MockResponse {
     pub fn new(tx: Sender<Response<Body>>) -> Self {
-        MockResponse { tx }
+        MockResponse {
+            tx,
+            headers: HeaderMap::new(),
+        }
     }
 }
 
@@ -43,11 +47,12 @@ unsafe impl Sync for MockResponse {}
 
 impl ServerResponseTrait for MockResponse {
     fn add_header(&mut self, key: HeaderName, value: HeaderValue) {
-        println!("add header,{},{:?}", key, value);
+        self.headers.insert(key, value);
     }
 
     // TODO
-    fn fire_result(&mut self, result: Response<Body>) -> CellResult<()> {
+    fn fire_result(&mut self, mut result: Response<Body>) -> CellResult<()> {
+        result.headers_mut().extend(self.headers.drain());
         self.tx
             .send(result)
             .and_then(|_| Ok(()))
@@ -37,7 +37,8 @@ impl<'a> ContextResponseWrapper<'a> {
         self.body = Some(b);
         self
     }
-    pub fn with_header(mut self) -> Self {
+    pub fn with_header(mut self, key: &str, value: &str) -> Self {
+        self.headers.insert(String::from(key), String::from(value));
         self
     }
 }
@@ -1,3 +1,4 @@
+use bytes::Bytes;
 use cell_core::core::ProtocolID;
 use cell_core::request::ServerRequestTrait;
 use hyper::{Body, Method, Request, Response, Server, StatusCode};
@@ -7,6 +8,7 @@ use std::net::SocketAddr;
 pub struct HttpRequest {
     pub request: Request<Body>,
     pub remote_addr: String,
+    pub body: Bytes,
 }
 
 unsafe impl Send for HttpRequest {}
@@ -17,8 +19,13 @@ impl HttpRequest {
         Self {
             request,
             remote_addr,
+            body: Bytes::new(),
         }
     }
+    pub fn with_body(mut self, body: Bytes) -> Self {
+        self.body = body;
+        self
+    }
 }
 
 impl ServerRequestTrait for HttpRequest {
@@ -32,4 +39,10 @@ impl ServerRequestTrait for HttpRequest {
     fn get_string_protocol(&self) -> String {
         self.request.uri().to_string()
     }
+    fn get_body(&self) -> Option<Bytes> {
+        Some(self.body.clone())
+    }
+    fn get_query(&self) -> Option<String> {
+        self.request.uri().query().map(String::from)
+    }
 }
@@ -2,7 +2,7 @@ use cell_core::cerror::CellResult;
 use cell_core::request::ServerResponseTrait;
 use futures::*;
 use http::header::HeaderName;
-use http::{HeaderValue, Response};
+use http::{HeaderMap, HeaderValue, Response};
 use hyper::Body;
 use std::any::Any;
 use std::sync::mpsc::Sender;
@@ -12,6 +12,8 @@ use tokio::sync::oneshot;
 pub struct HttpResponse {
     // tx: oneshot::Sender<Response<Body>>,
     txx: Sender<Response<Body>>,
+    // applied to the hyper response in fire_result
+    headers: HeaderMap,
 }
 
 unsafe impl Send for HttpResponse {}
@@ -20,15 +22,21 @@ unsafe impl Sync for HttpResponse {}
 
 impl HttpResponse {
     pub fn new(txxxx: Sender<Response<Body>>) -> Self {
-        Self { txx: txxxx }
+        Self {
+            txx: txxxx,
+            headers: HeaderMap::new(),
+        }
     }
 }
 
 impl ServerResponseTrait for HttpResponse {
-    fn add_header(&mut self, key: HeaderName, value: HeaderValue) {}
+    fn add_header(&mut self, key: HeaderName, value: HeaderValue) {
+        self.headers.insert(key, value);
+    }
 
-    fn fire_result(&mut self, result: Response<Body>) -> CellResult<()> {
+    fn fire_result(&mut self, mut result: Response<Body>) -> CellResult<()> {
         let (tx, rx) = std::sync::mpsc::channel::<Response<Body>>();
+        result.headers_mut().extend(self.headers.drain());
         self.txx.send(result);
         // TODO ,use another channel ,because of the ownship
         // self.tx.send(result);
@@ -39,3 +47,21 @@ impl ServerResponseTrait for HttpResponse {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::response::HttpResponse;
+    use cell_core::request::ServerResponseTrait;
+    use http::header::CONTENT_TYPE;
+    use http::{HeaderValue, Response};
+    use hyper::Body;
+
+    #[test]
+    fn test_add_header() {
+        let (txx, rxx) = std::sync::mpsc::channel::<Response<Body>>();
+        let mut resp = HttpResponse::new(txx);
+        resp.add_header(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        resp.fire_result(Response::new(Body::empty())).unwrap();
+        assert_eq!(rxx.recv().unwrap().headers()[CONTENT_TYPE], "text/plain");
+    }
+}
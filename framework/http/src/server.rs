@@ -11,6 +11,9 @@ use cell_core::request::MockRequest;
 use cell_core::selector::{CommandSelector, SelectorStrategy};
 use futures::future::ok;
 use futures::TryStreamExt;
+use http_body::{LengthLimitError, Limited};
+use hyper::body::Bytes;
+use hyper::header::CONTENT_LENGTH;
 use hyper::server::conn::AddrStream;
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Method, Request, Response, Server, StatusCode};
@@ -30,6 +33,9 @@ module_enums!(
     (HTTP_SERVER,1,&logsdk::common::LogLevel::Info);
 );
 
+// larger request bodies are answered with 413 and never dispatched
+pub const MAX_BODY_SIZE: usize = 4 * 1024 * 1024;
+
 pub struct HttpServer {
     dispatcher: DefaultDispatcher<'static, 'static>,
 }
@@ -145,15 +151,54 @@ pub struct ChannelWrapper {
     Ret: Option<Response<Body>>,
 }
 
+// the body is read up front, commands are executed synchronously
+async fn read_body(parts: &http::request::Parts, body: Body) -> Result<Bytes, StatusCode> {
+    let declared = parts
+        .headers
+        .get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    if declared.map_or(false, |len| len > MAX_BODY_SIZE) {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+    hyper::body::to_bytes(Limited::new(body, MAX_BODY_SIZE))
+        .await
+        .map_err(|e| {
+            if e.downcast_ref::<LengthLimitError>().is_some() {
+                StatusCode::PAYLOAD_TOO_LARGE
+            } else {
+                cerror!(
+                    ModuleEnumsStruct::HTTP_SERVER,
+                    "读取请求体失败:{}",
+                    e.to_string()
+                );
+                StatusCode::BAD_REQUEST
+            }
+        })
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    let mut ret = Response::new(Body::empty());
+    *ret.status_mut() = status;
+    ret
+}
+
 pub async fn async_hyper_service_fn(
     mut server: Arc<HttpServer>,
     req: Request<Body>,
     remote_addr: SocketAddr,
 ) -> Result<Response<Body>, std::io::Error> {
+    let (parts, body) = req.into_parts();
+    let body = match read_body(&parts, body).await {
+        Ok(body) => body,
+        Err(status) => return Ok(status_response(status)),
+    };
     let (tx, rx) = oneshot::channel();
     let (txx, rxx) = std::sync::mpsc::channel::<Response<Body>>();
     tokio::spawn(async move {
-        let http_req = Box::new(HttpRequest::new(req, remote_addr.ip().to_string()));
+        let req = Request::from_parts(parts, Body::empty());
+        let http_req =
+            Box::new(HttpRequest::new(req, remote_addr.ip().to_string()).with_body(body));
         let http_resp = Box::new(HttpResponse::new(txx));
         let ctx = DispatchContext::new(http_req, http_resp);
         server.dispatcher.dispatch(ctx).await;
@@ -201,10 +246,11 @@ mod tests {
     use crate::channel::HttpChannel;
     use crate::dispatcher::HttpDispatcher;
     use crate::selector::HttpSelector;
-    use crate::server::HttpServer;
+    use crate::server::{read_body, HttpServer, MAX_BODY_SIZE};
     use cell_core::command::mock_command;
     use cell_core::dispatcher::DefaultDispatcher;
     use cell_core::selector::{CommandSelector, SelectorRequest, SelectorStrategy};
+    use hyper::{Body, Request, StatusCode};
     use pipeline2::pipeline2::{ClosureExecutor, DefaultReactorExecutor, PipelineBuilder};
     use std::sync::Arc;
     use std::thread;
@@ -217,6 +263,29 @@ mod tests {
         assert_eq!(result, 4);
     }
 
+    #[test]
+    fn test_read_body() {
+        let read = |req: Request<Body>| {
+            let (parts, body) = req.into_parts();
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(read_body(&parts, body))
+        };
+        let ok = Request::new(Body::from("{\"a\":1}"));
+        assert_eq!(read(ok).unwrap().as_ref(), b"{\"a\":1}");
+
+        // 请求头里没有 content-length 也要限制
+        let big = Request::new(Body::from(vec![0u8; MAX_BODY_SIZE + 1]));
+        assert_eq!(read(big).unwrap_err(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let declared = Request::builder()
+            .header("content-length", (MAX_BODY_SIZE + 1).to_string())
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(read(declared).unwrap_err(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
     #[test]
     fn test_http_server() {
         let mut selector = HttpSelector::default();
@@ -0,0 +1,259 @@
+// cell-config keygen | encrypt <VALUE> | sealed | modules | dump | diff <LEFT> <RIGHT>
+use clap::{Arg, ArgMatches, Command};
+use configuration::error::{ConfigurationError, ConfigurationResult};
+use configuration::inspect;
+use configuration::manager::Manager;
+use configuration::plugin::{KeepSealed, SecretPlugin};
+use configuration::seal::{SealKey, KEY_ENV, KEY_FILE_ENV};
+use std::io::Write;
+use std::path::Path;
+use std::process::exit;
+use std::sync::Arc;
+
+const CONFIG_ROOT: &'static str = "config-root";
+const CONFIG_TYPE: &'static str = "config-type";
+const KEY_FILE: &'static str = "key-file";
+
+fn repo_args<'a>() -> Vec<Arg<'a>> {
+    vec![
+        Arg::new(CONFIG_ROOT)
+            .long(CONFIG_ROOT)
+            .help("the directory containing root.json")
+            .takes_value(true)
+            .default_value("./config"),
+        Arg::new(CONFIG_TYPE)
+            .long(CONFIG_TYPE)
+            .help("the config type ,defaults to the defaultType of root.json")
+            .takes_value(true),
+    ]
+}
+
+// the config type is taken from root.json when not given
+fn open_repo(m: &ArgMatches) -> ConfigurationResult<Manager> {
+    open(m.value_of(CONFIG_ROOT).unwrap(), m.value_of(CONFIG_TYPE))
+}
+
+// loaded like a service loads it ,except that sealed values stay sealed ,the output is redacted anyway
+fn open(root: &str, config_type: Option<&str>) -> ConfigurationResult<Manager> {
+    let mut manager = Manager::prepare(root, config_type, &[])?;
+    // 只看密文 ,不需要 key ,root.json 里 secret 的配置照样接受
+    manager
+        .get_configuration_mut()
+        .register_plugin(SecretPlugin::new(Arc::new(KeepSealed {})));
+    manager.initialize()?;
+    Ok(manager)
+}
+
+fn encrypt(m: &ArgMatches, out: &mut dyn Write) -> ConfigurationResult<()> {
+    let key = match m.value_of(KEY_FILE) {
+        Some(f) => SealKey::from_file(Path::new(f))?,
+        None => SealKey::from_env()?.ok_or_else(|| {
+            ConfigurationError::StringError(format!(
+                "no key ,pass --{} or set {} or {}",
+                KEY_FILE, KEY_ENV, KEY_FILE_ENV
+            ))
+        })?,
+    };
+    writeln!(out, "{}", key.seal(m.value_of("value").unwrap())?)?;
+    Ok(())
+}
+
+fn sealed(m: &ArgMatches, out: &mut dyn Write) -> ConfigurationResult<()> {
+    let manager = open_repo(m)?;
+    for (module, paths) in manager.get_configuration().sealed_keys()? {
+        for p in paths {
+            writeln!(out, "{}.{}", module, p)?;
+        }
+    }
+    Ok(())
+}
+
+fn print_json<T: serde::Serialize>(out: &mut dyn Write, v: &T) -> ConfigurationResult<()> {
+    writeln!(out, "{}", serde_json::to_string_pretty(v)?)?;
+    Ok(())
+}
+
+fn modules(m: &ArgMatches, out: &mut dyn Write) -> ConfigurationResult<()> {
+    print_json(out, &open_repo(m)?.get_configuration().module_sources())
+}
+
+fn dump(m: &ArgMatches, out: &mut dyn Write) -> ConfigurationResult<()> {
+    let manager = open_repo(m)?;
+    let cfg = manager.get_configuration();
+    match m.value_of("module") {
+        Some(module) => print_json(out, &cfg.redacted_value(module)?),
+        None => print_json(out, &inspect::effective(cfg)?),
+    }
+}
+
+fn diff(m: &ArgMatches, out: &mut dyn Write) -> ConfigurationResult<()> {
+    let root = m.value_of(CONFIG_ROOT).unwrap();
+    let left = open(root, m.value_of("left"))?;
+    let right = open(root, m.value_of("right"))?;
+    for d in inspect::diff(left.get_configuration(), right.get_configuration())? {
+        writeln!(out, "{}", d)?;
+    }
+    Ok(())
+}
+
+fn cli() -> Command<'static> {
+    Command::new("cell-config")
+        .about("tools for the configuration repo")
+        .subcommand_required(true)
+        .subcommand(Command::new("keygen").about("print a new base64 key for sealed values"))
+        .subcommand(
+            Command::new("encrypt")
+                .about("seal a value as ENC[AES256_GCM,...]")
+                .arg(
+                    Arg::new(KEY_FILE)
+                        .long(KEY_FILE)
+                        .help("the key file ,defaults to the key from the environment")
+                        .takes_value(true),
+                )
+                .arg(Arg::new("value").required(true)),
+        )
+        .subcommand(
+            Command::new("sealed")
+                .about("list the keys holding sealed values")
+                .args(repo_args()),
+        )
+        .subcommand(
+            Command::new("modules")
+                .about("print where each module is loaded from")
+                .args(repo_args()),
+        )
+        .subcommand(
+            Command::new("dump")
+                .about("print the effective values with the secrets redacted")
+                .args(repo_args())
+                .arg(
+                    Arg::new("module")
+                        .long("module")
+                        .help("only this module")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("compare the effective values of two config types")
+                .arg(repo_args().remove(0))
+                .arg(Arg::new("left").required(true))
+                .arg(Arg::new("right").required(true)),
+        )
+}
+
+fn run(matches: &ArgMatches, out: &mut dyn Write) -> ConfigurationResult<()> {
+    match matches.subcommand() {
+        Some(("keygen", _)) => {
+            writeln!(out, "{}", SealKey::generate().to_base64())?;
+            Ok(())
+        }
+        Some(("encrypt", m)) => encrypt(m, out),
+        Some(("sealed", m)) => sealed(m, out),
+        Some(("modules", m)) => modules(m, out),
+        Some(("dump", m)) => dump(m, out),
+        Some(("diff", m)) => diff(m, out),
+        _ => unreachable!(),
+    }
+}
+
+fn main() {
+    let matches = cli().get_matches();
+    if let Err(e) = run(&matches, &mut std::io::stdout()) {
+        eprintln!("{}", e);
+        exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{cli, run};
+    use configuration::seal::SealKey;
+    use serde_json::json;
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn temp_repo(name: &str, root: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cell-config-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("Default")).unwrap();
+        fs::write(dir.join("root.json"), root).unwrap();
+        dir
+    }
+
+    fn run_cli(args: &[&str]) -> String {
+        let mut out = Vec::new();
+        run(&cli().get_matches_from(args), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_sealed() {
+        let dir = temp_repo(
+            "sealed",
+            r#"{"types":{"Default":{"parent":null}},"defaultType":"Default","configs":[{"modules":{"nacos":"nacos.json"},"schema":null}],
+               "plugins":{"secret":{"keyFile":"config.key"}}}"#,
+        );
+        // the key file is not needed to list the sealed keys
+        let sealed = SealKey::generate().seal("p@ss").unwrap();
+        fs::write(
+            dir.join("Default/nacos.json"),
+            format!(r#"{{"serverAddr":"a","auth":{{"password":"{}"}}}}"#, sealed),
+        )
+        .unwrap();
+
+        let root = dir.to_string_lossy().to_string();
+        let out = run_cli(&["cell-config", "sealed", "--config-root", root.as_str()]);
+        assert_eq!(out, "nacos.auth.password\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plugins() {
+        let dir = temp_repo(
+            "plugins",
+            r#"{"types":{"Default":{"parent":null},"test1":{"parent":"Default"}},"defaultType":"Default","configs":[{"modules":{"nacos":"nacos.json"},"schema":null}],
+               "plugins":{"fileKv":{"path":"remote/kv.json"},"template":{"vars":{"region":"cn"}}}}"#,
+        );
+        fs::create_dir_all(dir.join("remote")).unwrap();
+        fs::create_dir_all(dir.join("test1")).unwrap();
+        fs::write(
+            dir.join("remote/kv.json"),
+            r#"{"nacos":{"namespace":"remote"}}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("Default/nacos.json"),
+            r#"{"serverAddr":"nacos.${region}"}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("test1/nacos.json"),
+            r#"{"serverAddr":"nacos.${configType}"}"#,
+        )
+        .unwrap();
+        let root = dir.to_string_lossy().to_string();
+
+        // the same values a service gets from Manager::prepare
+        let out = run_cli(&["cell-config", "dump", "--config-root", root.as_str()]);
+        let v: serde_json::Value = serde_json::from_str(out.as_str()).unwrap();
+        assert_eq!(
+            v,
+            json!({"nacos":{"serverAddr":"nacos.cn","namespace":"remote"}})
+        );
+
+        let out = run_cli(&["cell-config", "modules", "--config-root", root.as_str()]);
+        assert!(out.contains("nacos.json"), "{}", out);
+
+        let out = run_cli(&[
+            "cell-config",
+            "diff",
+            "--config-root",
+            root.as_str(),
+            "Default",
+            "test1",
+        ]);
+        assert_eq!(out, "~ nacos.serverAddr: \"nacos.cn\" -> \"nacos.test1\"\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
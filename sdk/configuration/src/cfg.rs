@@ -1,23 +1,54 @@
 use crate::enums::Schema;
 use crate::error::{ConfigurationError, ConfigurationResult};
+use crate::inspect::{redact, ModuleSource};
+use crate::json::strip_comments;
+use crate::jsonnet::{EXT_CONFIG_ROOT, EXT_CONFIG_TYPE};
+use crate::layer::Layers;
 use crate::manager::Manager;
+use crate::merge::{merge_with, ArrayPolicy};
 use crate::parser::{ConfigurationParser, DefaultParser, ParserEnums};
+use crate::plugin::{ConfigurationPlugin, PluginContext, SecretPlugin, SharedPlugin, SECRET};
+use crate::seal::sealed_paths;
+use crate::validate::{
+    apply_schema_defaults, unsupported_keywords, validate_schema, ModuleValidator, Violation,
+};
 use crate::value::ConfigValueTrait;
+use crate::CONFIGURATION;
+use logsdk::common::LogLevel;
 use serde::{Deserialize, Serialize};
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::rc::Rc;
+use std::sync::{Arc, Mutex, RwLock};
 
+// resolved module values ,shared with the watcher so reloads are visible to every clone
+type ModuleCache = Arc<RwLock<HashMap<String, serde_json::Value>>>;
+
+#[derive(Clone)]
 pub struct Configuration {
-    parser: HashMap<Schema, Rc<RefCell<DefaultParser>>>,
+    parser: HashMap<Schema, Arc<Mutex<DefaultParser>>>,
     config_module: HashMap<String, Vec<String>>,
 
     modules: HashMap<String, ConfigModule>,
 
     config_types: Vec<String>,
 
+    layers: Layers,
+    cache: ModuleCache,
+    // json schemas from root.json and validators registered in code ,checked at initialize
+    schemas: HashMap<String, serde_json::Value>,
+    validators: HashMap<String, ModuleValidator>,
+    plugins: Vec<SharedPlugin>,
+    // the registered plugins root.json has settings for
+    enabled_plugins: Vec<SharedPlugin>,
+    // the config type and its parents ,parents first
+    inheritance: Vec<String>,
+    // repos mounted in code ,added to the `repos` of root.json
+    mounts: Vec<RepoMount>,
+    // the primary repo first ,then the mounted ones ,resolved at initialize
+    repos: Vec<RepoMount>,
+
     repo_root: Option<PathBuf>,
     config_type: Option<String>,
     initialized: bool,
@@ -31,6 +62,15 @@ impl Configuration {
             config_module: Default::default(),
             modules: Default::default(),
             config_types: Default::default(),
+            layers: Default::default(),
+            cache: Default::default(),
+            schemas: Default::default(),
+            validators: Default::default(),
+            plugins: Default::default(),
+            enabled_plugins: Default::default(),
+            inheritance: Default::default(),
+            mounts: Default::default(),
+            repos: Default::default(),
             repo_root: Some(repo_root),
             config_type: Some(String::from(config_type)),
             initialized: false,
@@ -39,38 +79,338 @@ impl Configuration {
         ret
     }
     fn init(&mut self) {
-        let json = Rc::new(RefCell::new(DefaultParser::new(Schema::JSON)));
-        let toml = Rc::new(RefCell::new(DefaultParser::new(Schema::TOML)));
+        let json = Arc::new(Mutex::new(DefaultParser::new(Schema::JSON)));
+        let toml = Arc::new(Mutex::new(DefaultParser::new(Schema::TOML)));
+        let yaml = Arc::new(Mutex::new(DefaultParser::new(Schema::YAML)));
+        let root = self
+            .repo_root
+            .as_ref()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        let jsonnet = Arc::new(Mutex::new(
+            DefaultParser::new(Schema::JSONNET)
+                .with_ext_var(EXT_CONFIG_TYPE, self.config_type.as_ref().unwrap())
+                .with_ext_var(EXT_CONFIG_ROOT, root.as_str()),
+        ));
         self.register_parser(Schema::JSON, json);
         self.register_parser(Schema::TOML, toml);
+        self.register_parser(Schema::YAML, yaml);
+        self.register_parser(Schema::JSONNET, jsonnet);
+        self.register_plugin(SecretPlugin::default());
     }
-    fn register_parser(&mut self, s: Schema, p: Rc<RefCell<DefaultParser>>) {
+    fn register_parser(&mut self, s: Schema, p: Arc<Mutex<DefaultParser>>) {
         self.parser.insert(s, p);
     }
 
+    // programmatic defaults ,the lowest layer below the files
+    pub fn set_default<T: Serialize>(
+        &mut self,
+        module_name: &str,
+        value: &T,
+    ) -> ConfigurationResult<()> {
+        self.cache.write().unwrap().clear();
+        self.layers.set_default(module_name, value)
+    }
+
+    // `module.key=value` ,the highest layer above the environment variables
+    pub fn add_overrides(&mut self, sets: &[String]) -> ConfigurationResult<()> {
+        self.cache.write().unwrap().clear();
+        self.layers.add_sets(sets)
+    }
+
+    // mount another repo before initialize ,see RepoMount
+    pub fn mount<P: AsRef<Path>>(&mut self, repo: P, precedence: i32) {
+        self.mounts.push(RepoMount {
+            path: repo.as_ref().to_path_buf(),
+            precedence,
+        });
+    }
+
+    // register before initialize ,the rules add up with the json schema of the module
+    pub fn register_validator(&mut self, module_name: &str, validator: ModuleValidator) {
+        self.validators.insert(String::from(module_name), validator);
+    }
+
+    // register before initialize ,the plugin is enabled by an entry under `plugins` in root.json
+    // a plugin with the same name ,builtin ones included ,is replaced
+    pub fn register_plugin<P: ConfigurationPlugin + 'static>(&mut self, plugin: P) {
+        self.remove_plugin(plugin.name());
+        self.plugins.push(Arc::new(Mutex::new(Box::new(plugin))));
+    }
+
+    pub fn remove_plugin(&mut self, name: &str) {
+        self.plugins.retain(|p| p.lock().unwrap().name() != name);
+    }
+
+    fn init_plugins(
+        &self,
+        settings: &HashMap<String, serde_json::Value>,
+    ) -> ConfigurationResult<Vec<SharedPlugin>> {
+        let names: Vec<String> = self
+            .plugins
+            .iter()
+            .map(|p| String::from(p.lock().unwrap().name()))
+            .collect();
+        let mut unknown: Vec<&String> = settings.keys().filter(|k| !names.contains(k)).collect();
+        unknown.sort();
+        if let Some(name) = unknown.first() {
+            return Err(ConfigurationError::UnknownPlugin {
+                name: (*name).clone(),
+                registered: names,
+            });
+        }
+        let ctx = PluginContext {
+            repo_root: self.repo_root.clone().unwrap_or_default(),
+            config_type: self.config_type.clone().unwrap_or_default(),
+        };
+        let mut ret = Vec::new();
+        for (p, name) in self.plugins.iter().zip(names.iter()) {
+            let mut plugin = p.lock().unwrap();
+            let s = match settings.get(name) {
+                Some(s) => s,
+                None if plugin.enabled_by_default() => &serde_json::Value::Null,
+                None => continue,
+            };
+            plugin.init(s, &ctx)?;
+            ret.push(p.clone());
+        }
+        // 解密放在最后 ,其它插件(比如 template)看不到明文
+        ret.sort_by_key(|p| p.lock().unwrap().name() == SECRET);
+        Ok(ret)
+    }
+
+    fn plugin_value(&self, module_name: &str) -> ConfigurationResult<Option<serde_json::Value>> {
+        let mut ret = None;
+        for p in &self.enabled_plugins {
+            if let Some(v) = p.lock().unwrap().load(module_name)? {
+                merge_with(
+                    ret.get_or_insert(serde_json::Value::Null),
+                    v,
+                    ArrayPolicy::Replace,
+                );
+            }
+        }
+        Ok(ret)
+    }
+
+    pub fn validate_module(&self, module_name: &str, value: &serde_json::Value) -> Vec<Violation> {
+        let mut ret = Vec::new();
+        if let Some(s) = self.schemas.get(module_name) {
+            ret.extend(validate_schema(module_name, s, value));
+        }
+        if let Some(v) = self.validators.get(module_name) {
+            ret.extend(v.validate(module_name, value));
+        }
+        ret
+    }
+
+    // every module with a schema or a validator ,all the violations are returned together
+    fn validate_all(&self) -> ConfigurationResult<()> {
+        let mut names: Vec<&String> = self.schemas.keys().chain(self.validators.keys()).collect();
+        names.sort();
+        names.dedup();
+        let mut violations = Vec::new();
+        for name in names {
+            if let Some(s) = self.schemas.get(name) {
+                violations.extend(unsupported_keywords(name, s));
+            }
+            match self.load_module(name) {
+                Ok(v) => {
+                    violations.extend(self.validate_module(name, &v));
+                    self.store(name, v);
+                }
+                Err(e) => violations.push(Violation {
+                    module: name.clone(),
+                    json_path: String::from("."),
+                    message: e.to_string(),
+                }),
+            }
+        }
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigurationError::ValidationFailed(violations))
+        }
+    }
+
     pub fn get_config<T: serde::de::DeserializeOwned + Clone>(
         &self,
         module_name: &str,
     ) -> ConfigurationResult<T> {
-        let module_name = String::from(module_name);
-        let module = self.get_module(module_name.clone());
-        if module.is_none() {
-            return Err(ConfigurationError::ModuleNotExists);
+        let v = match self.cached(module_name) {
+            Some(v) => v,
+            None => {
+                let v = self.load_module(module_name)?;
+                self.store(module_name, v.clone());
+                v
+            }
+        };
+        serde_path_to_error::deserialize::<_, T>(v).map_err(|e| ConfigurationError::InvalidModule {
+            module: String::from(module_name),
+            json_path: e.path().to_string(),
+            message: e.into_inner().to_string(),
+        })
+    }
+
+    // reads the files again ,bypassing the cache
+    pub fn load_module(&self, module_name: &str) -> ConfigurationResult<serde_json::Value> {
+        let mut ret = self.load_layered(module_name)?;
+        for p in &self.enabled_plugins {
+            p.lock().unwrap().transform(module_name, &mut ret)?;
+        }
+        Ok(ret)
+    }
+
+    pub fn module_names(&self) -> Vec<String> {
+        sorted_keys(&self.modules)
+    }
+
+    // module => paths of the `ENC[...]` values ,found before the secret plugin decrypts them
+    pub fn sealed_keys(&self) -> ConfigurationResult<BTreeMap<String, Vec<String>>> {
+        let mut ret = BTreeMap::new();
+        for name in self.module_names() {
+            let paths = sealed_paths(&self.load_layered(name.as_str())?);
+            if !paths.is_empty() {
+                ret.insert(name, paths);
+            }
+        }
+        Ok(ret)
+    }
+
+    // the effective value with the sealed values and credential-like keys redacted
+    pub fn redacted_value(&self, module_name: &str) -> ConfigurationResult<serde_json::Value> {
+        let sealed = sealed_paths(&self.load_layered(module_name)?);
+        let mut ret = self.load_module(module_name)?;
+        redact(&mut ret, sealed.as_slice());
+        Ok(ret)
+    }
+
+    pub fn module_sources(&self) -> Vec<ModuleSource> {
+        self.module_names()
+            .into_iter()
+            .map(|name| {
+                let module = &self.modules[&name];
+                ModuleSource {
+                    module: name.clone(),
+                    schema: module.schema.to_string(),
+                    repo: module.repo.clone(),
+                    module_path: module.module_path.clone(),
+                    files: self.existing_paths(module),
+                    module_due_path: module.module_due_path.clone(),
+                    shadowed: module.shadowed.clone(),
+                }
+            })
+            .collect()
+    }
+
+    // every layer merged ,before the plugins transform the value
+    fn load_layered(&self, module_name: &str) -> ConfigurationResult<serde_json::Value> {
+        let plugin_value = self.plugin_value(module_name)?;
+        let has_default = self.layers.has_module(module_name) || plugin_value.is_some();
+        let mut file_value = match self.get_module(String::from(module_name)) {
+            Some(module) => {
+                let paths = self.existing_paths(module);
+                if paths.is_empty() && has_default {
+                    None
+                } else {
+                    Some(self.get_module_value(module, module_name, paths)?)
+                }
+            }
+            None if has_default => None,
+            None => return Err(ConfigurationError::ModuleNotExists),
+        };
+        if let Some(v) = plugin_value {
+            merge_with(
+                file_value.get_or_insert(serde_json::Value::Null),
+                v,
+                ArrayPolicy::Replace,
+            );
         }
-        let module = module.unwrap();
-        self.get_config_object(&module.schema, module_name.clone())
+        let mut ret = self.layers.apply(module_name, file_value)?;
+        // schema 里的 default 只补缺失的 key
+        if let Some(s) = self.schemas.get(module_name) {
+            apply_schema_defaults(s, &mut ret);
+        }
+        Ok(ret)
+    }
+
+    pub(crate) fn cached(&self, module_name: &str) -> Option<serde_json::Value> {
+        self.cache.read().unwrap().get(module_name).cloned()
     }
 
-    fn get_config_object<T: serde::de::DeserializeOwned + Clone + Sized>(
+    pub(crate) fn store(&self, module_name: &str, value: serde_json::Value) {
+        self.cache
+            .write()
+            .unwrap()
+            .insert(String::from(module_name), value);
+    }
+
+    fn get_module_value(
         &self,
-        schema: &Schema,
-        module_name: String,
-    ) -> ConfigurationResult<T> {
-        let module = self.get_module(module_name.clone()).unwrap();
-        let parser = self.parser.get(schema).map_or_else(|| panic!(), |v| v);
-        let mut parser = parser.borrow_mut();
-        let v = parser.parse_from(module_name.clone(), module.module_full_path.clone())?;
-        v.as_object()
+        module: &ConfigModule,
+        module_name: &str,
+        paths: Vec<PathBuf>,
+    ) -> ConfigurationResult<serde_json::Value> {
+        let parser = self
+            .parser
+            .get(&module.schema)
+            .ok_or_else(|| ConfigurationError::UnknownSchema(module.schema.to_string()))?;
+        let mut parser = parser.lock().unwrap();
+        if paths.is_empty() {
+            return Err(ConfigurationError::ModuleFileNotFound {
+                module: String::from(module_name),
+                candidates: self.candidate_paths(module),
+            });
+        }
+        // 父类型在前 ,子类型只需要写要覆盖的字段
+        let mut ret = serde_json::Value::Null;
+        for path in paths {
+            let v = parser
+                .parse_from(String::from(module_name), path.clone())
+                .and_then(|v| v.as_object())
+                .map_err(|e| e.in_file(&path))?;
+            merge_with(&mut ret, v, module.array_policy);
+        }
+        Ok(ret)
+    }
+
+    fn candidate_paths(&self, module: &ConfigModule) -> Vec<PathBuf> {
+        self.inheritance
+            .iter()
+            .map(|t| module.repo.join(t).join(&module.module_path))
+            .collect()
+    }
+
+    // 每次都重新检查 ,运行期间子类型新增的文件也能生效
+    fn existing_paths(&self, module: &ConfigModule) -> Vec<PathBuf> {
+        self.candidate_paths(module)
+            .into_iter()
+            .filter(|p| p.exists())
+            .collect()
+    }
+
+    // every file that may contribute to a module ,existing or not ,keyed by canonical path
+    pub fn watched_files(&self) -> HashMap<PathBuf, Vec<String>> {
+        let mut ret: HashMap<PathBuf, Vec<String>> = HashMap::new();
+        for (name, module) in &self.modules {
+            let repo = fs::canonicalize(&module.repo).unwrap_or_else(|_| module.repo.clone());
+            for t in &self.inheritance {
+                ret.entry(repo.join(t).join(&module.module_path))
+                    .or_default()
+                    .push(name.clone());
+            }
+        }
+        ret
+    }
+
+    pub fn get_repo_root(&self) -> Option<&PathBuf> {
+        self.repo_root.as_ref()
+    }
+
+    // the primary repo and the mounted ones ,empty before initialize
+    pub fn get_repos(&self) -> &Vec<RepoMount> {
+        &self.repos
     }
 
     fn get_module(&self, module_name: String) -> Option<&ConfigModule> {
@@ -80,69 +420,132 @@ impl Configuration {
     pub fn initialize(&mut self) -> ConfigurationResult<()> {
         let root_path = self.repo_root.as_ref().unwrap().clone();
         let config_type = self.config_type.as_ref().unwrap().clone();
-        let root_json = root_path.clone().join("root.json");
-        let data = fs::read(root_json).unwrap();
-        let root_config: RootConfig =
-            serde_json::from_slice::<RootConfig>(data.as_slice()).unwrap();
+        let root_config = RootConfig::load(&root_path.join("root.json"))?;
 
-        let mut repos: HashMap<PathBuf, RootConfig> = Default::default();
         let config_types = root_config.get_config_types();
         if config_types.get(&config_type.clone()).is_none() {
-            panic!()
+            return Err(ConfigurationError::UnknownConfigType {
+                config_type,
+                valid: sorted_keys(&config_types),
+            });
+        }
+        let enabled_plugins = self.init_plugins(&root_config.plugins)?;
+
+        // config types and plugins only come from the primary repo ,the `repos` of a mounted repo are ignored
+        let mut mounts = vec![RepoMount {
+            path: root_path.clone(),
+            precedence: 0,
+        }];
+        mounts.extend(root_config.repos.iter().map(|r| RepoMount {
+            path: root_path.join(&r.path),
+            precedence: r.precedence,
+        }));
+        mounts.extend(self.mounts.iter().cloned());
+        let mut repos = vec![(mounts[0].clone(), root_config)];
+        for m in mounts.iter().skip(1) {
+            if !m.path.is_dir() {
+                return Err(ConfigurationError::RootNotExists(m.path.clone()));
+            }
+            repos.push((m.clone(), RootConfig::load(&m.path.join("root.json"))?));
+        }
+        // 低优先级的先放 ,同名模块的 schema 由优先级高的覆盖
+        let mut by_precedence: Vec<&(RepoMount, RootConfig)> = repos.iter().collect();
+        by_precedence.sort_by_key(|(m, _)| m.precedence);
+        let mut schemas = HashMap::new();
+        for (m, root) in by_precedence {
+            schemas.extend(root.get_json_schemas(&m.path)?);
         }
-        repos.insert(root_path.clone(), root_config);
 
-        let inheritance = self.build_inheritance_list(&config_types);
-        self.build_module_path_map(&repos, &inheritance);
+        let inheritance = self.build_inheritance_list(&config_types)?;
+        self.build_module_path_map(&repos, &inheritance)?;
+        self.repos = mounts;
+        self.inheritance = inheritance;
+        self.schemas = schemas;
+        self.enabled_plugins = enabled_plugins;
+        self.cache.write().unwrap().clear();
 
         for k in &config_types {
             self.config_types.push(k.0.clone());
         }
+        self.layers.load_env(std::env::vars())?;
         self.initialized = true;
-        Ok(())
+        self.validate_all()
     }
 
+    // a module defined by several repos comes from the one with the highest precedence ,
+    // the others are kept in `shadowed` ,the same precedence is an error
     fn build_module_path_map(
         &mut self,
-        repos: &HashMap<PathBuf, RootConfig>,
+        repos: &[(RepoMount, RootConfig)],
         inheritance: &Vec<String>,
-    ) {
-        for (k, v) in repos {
-            let mut modules = v.get_modules();
-            let mut mut_iter = modules.iter_mut();
-            loop {
-                match mut_iter.next() {
-                    None => break,
-                    Some((k2, v2)) => {
-                        let mut module_full_path = Default::default();
-                        for type_def in inheritance {
-                            let temp = k.join(type_def).join(v2.module_full_path.clone());
-                            if !temp.exists() {
-                                continue;
-                            }
-                            if v2.module_due_path.is_none() {
-                                v2.set_due_path(temp.clone());
-                            }
-                            module_full_path = temp.clone();
-                        }
-                        v2.set_full_path(module_full_path);
+    ) -> ConfigurationResult<()> {
+        let mut candidates: HashMap<String, Vec<(i32, ConfigModule)>> = HashMap::new();
+        for (mount, root) in repos {
+            for (name, mut module) in root.get_modules()? {
+                module.set_repo(mount.path.clone());
+                let mut module_full_path = Default::default();
+                for type_def in inheritance {
+                    let temp = mount
+                        .path
+                        .join(type_def)
+                        .join(module.module_full_path.clone());
+                    if !temp.exists() {
+                        continue;
                     }
+                    if module.module_due_path.is_none() {
+                        module.set_due_path(temp.clone());
+                    }
+                    module.add_path(temp.clone());
+                    module_full_path = temp.clone();
                 }
+                module.set_full_path(module_full_path);
+                candidates
+                    .entry(name)
+                    .or_default()
+                    .push((mount.precedence, module));
             }
+        }
 
-            for (k, v) in modules {
-                self.modules.insert(k.clone(), v);
+        self.modules.clear();
+        for (name, mut list) in candidates {
+            // 稳定排序 ,同优先级保持挂载顺序
+            list.sort_by_key(|(p, _)| std::cmp::Reverse(*p));
+            let top = list[0].0;
+            if list.len() > 1 && list[1].0 == top {
+                return Err(ConfigurationError::ModuleConflict {
+                    module: name,
+                    repos: list
+                        .into_iter()
+                        .filter(|(p, _)| *p == top)
+                        .map(|(_, m)| m.repo)
+                        .collect(),
+                });
             }
+            let mut iter = list.into_iter();
+            let (_, mut module) = iter.next().unwrap();
+            module.shadowed = iter.map(|(_, m)| m.repo).collect();
+            if !module.shadowed.is_empty() {
+                cwarn!(
+                    CONFIGURATION,
+                    "module {} from {:?} shadows {:?}",
+                    name,
+                    module.repo,
+                    module.shadowed
+                );
+            }
+            self.modules.insert(name, module);
         }
+        Ok(())
     }
     fn build_inheritance_list(
         &mut self,
         config_types: &HashMap<String, Option<String>>,
-    ) -> Vec<String> {
+    ) -> ConfigurationResult<Vec<String>> {
         let mut ret = Vec::new();
         ret.push(self.config_type.as_ref().unwrap().clone());
 
         let config_type = self.config_type.as_ref().unwrap();
+        let mut current = config_type.clone();
         let mut parent = config_types.get(config_type);
         loop {
             match parent {
@@ -154,16 +557,45 @@ impl Configuration {
                         break;
                     }
                     let internal = p.as_ref().unwrap();
+                    if ret.contains(internal) {
+                        let mut cycle = ret.clone();
+                        cycle.reverse();
+                        cycle.push(internal.clone());
+                        return Err(ConfigurationError::InheritanceCycle(cycle));
+                    }
+                    if !config_types.contains_key(internal) {
+                        return Err(ConfigurationError::UnknownParentType {
+                            config_type: current,
+                            parent: internal.clone(),
+                            valid: sorted_keys(config_types),
+                        });
+                    }
                     ret.insert(0, internal.clone());
+                    current = internal.clone();
                     parent = config_types.get(internal);
                 }
             }
         }
 
-        ret
+        Ok(ret)
     }
 }
 
+fn sorted_keys<V>(m: &HashMap<String, V>) -> Vec<String> {
+    let mut ret: Vec<String> = m.keys().cloned().collect();
+    ret.sort();
+    ret
+}
+
+// a repo mounted next to the primary one ,which has precedence 0 ,
+// a module both define comes from the higher precedence
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RepoMount {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub precedence: i32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RootConfig {
     types: HashMap<String, Types>,
@@ -171,9 +603,54 @@ pub struct RootConfig {
     default_type: String,
     configs: Vec<ConfigNode>,
     plugins: HashMap<String, serde_json::Value>,
+    // other repos mounted next to this one ,paths relative to this repo
+    #[serde(default)]
+    repos: Vec<RepoMount>,
+    // module => inline schema ,or a schema file relative to the repo
+    #[serde(rename = "jsonSchemas", default)]
+    json_schemas: HashMap<String, serde_json::Value>,
 }
 
 impl RootConfig {
+    pub fn load(path: &Path) -> ConfigurationResult<Self> {
+        let data = fs::read(path).map_err(|e| ConfigurationError::ReadError {
+            path: path.to_path_buf(),
+            source: e,
+        })?;
+        let data = strip_comments(data.as_slice());
+        let de = &mut serde_json::Deserializer::from_slice(data.as_slice());
+        serde_path_to_error::deserialize::<_, RootConfig>(de).map_err(|e| {
+            ConfigurationError::from_json(path, e.path().to_string().as_str(), e.inner())
+        })
+    }
+
+    pub fn get_json_schemas(
+        &self,
+        repo: &Path,
+    ) -> ConfigurationResult<HashMap<String, serde_json::Value>> {
+        let mut ret = HashMap::new();
+        for (module, schema) in &self.json_schemas {
+            let schema = match schema {
+                serde_json::Value::String(p) => {
+                    let path = repo.join(p);
+                    let data = fs::read(&path).map_err(|e| ConfigurationError::ReadError {
+                        path: path.clone(),
+                        source: e,
+                    })?;
+                    serde_json::from_slice(strip_comments(data.as_slice()).as_slice())
+                        .map_err(|e| ConfigurationError::from_json(&path, ".", &e))?
+                }
+                inline => inline.clone(),
+            };
+            ret.insert(module.clone(), schema);
+        }
+        Ok(ret)
+    }
+
+    pub fn get_default_type(&self) -> &str {
+        self.default_type.as_str()
+    }
+
     pub fn get_config_types(&self) -> HashMap<String, Option<String>> {
         let mut ret = HashMap::default();
         for (k, v) in self.types.clone() {
@@ -187,7 +664,7 @@ impl RootConfig {
         return ret;
     }
 
-    pub fn get_modules(&self) -> HashMap<String, ConfigModule> {
+    pub fn get_modules(&self) -> ConfigurationResult<HashMap<String, ConfigModule>> {
         let mut ret = HashMap::new();
         for i in 0..self.configs.len() {
             let data = self.configs.get(i);
@@ -204,28 +681,53 @@ impl RootConfig {
             for (module_name, module_path) in module {
                 ret.insert(
                     module_name,
-                    ConfigModule::new(PathBuf::from(module_path), schema.clone()),
+                    ConfigModule::new(PathBuf::from(module_path), schema.clone())?
+                        .with_array_policy(data.array_merge),
                 );
             }
         }
 
-        ret
+        Ok(ret)
     }
 }
 
+#[derive(Clone)]
 pub struct ConfigModule {
+    // as declared in root.json ,relative to `repo/<type>`
+    pub module_path: PathBuf,
+    pub repo: PathBuf,
     pub module_full_path: PathBuf,
     pub module_due_path: Option<PathBuf>,
+    // every existing file along the inheritance list ,parents first
+    pub module_paths: Vec<PathBuf>,
+    pub array_policy: ArrayPolicy,
+    // repos with lower precedence that define the module too
+    pub shadowed: Vec<PathBuf>,
     schema: Schema,
 }
 
 impl ConfigModule {
-    pub fn new(module_path: PathBuf, schema: String) -> Self {
-        Self {
+    pub fn new(module_path: PathBuf, schema: String) -> ConfigurationResult<Self> {
+        Ok(Self {
+            module_path: module_path.clone(),
+            repo: Default::default(),
             module_full_path: module_path,
             module_due_path: Default::default(),
-            schema: Schema::from(schema),
-        }
+            module_paths: Default::default(),
+            array_policy: Default::default(),
+            shadowed: Default::default(),
+            schema: Schema::try_from(schema)?,
+        })
+    }
+    pub fn with_array_policy(mut self, policy: ArrayPolicy) -> Self {
+        self.array_policy = policy;
+        self
+    }
+    pub fn set_repo(&mut self, repo: PathBuf) {
+        self.repo = repo;
+    }
+    pub fn add_path(&mut self, path: PathBuf) {
+        self.module_paths.push(path);
     }
     pub fn set_full_path(&mut self, full: PathBuf) {
         self.module_full_path = full;
@@ -245,4 +747,7 @@ struct ConfigNode {
     #[serde(rename = "modules")]
     modules: HashMap<String, String>,
     schema: Option<String>,
+    // replace | append ,how arrays of a child type combine with its parent
+    #[serde(rename = "arrayMerge", default)]
+    array_merge: ArrayPolicy,
 }
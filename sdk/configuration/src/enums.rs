@@ -1,16 +1,24 @@
-#[derive(Eq, PartialEq, Hash)]
+use crate::error::ConfigurationError;
+use std::convert::TryFrom;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
 pub enum Schema {
     JSON,
     TOML,
+    JSONNET,
+    YAML,
 }
-impl From<String> for Schema {
-    fn from(value: String) -> Self {
-        if value.as_str() == "json" {
-            return Schema::JSON;
-        } else if value.as_str() == "toml" {
-            return Schema::TOML;
+impl TryFrom<String> for Schema {
+    type Error = ConfigurationError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "json" => Ok(Schema::JSON),
+            "toml" => Ok(Schema::TOML),
+            "jsonnet" => Ok(Schema::JSONNET),
+            "yaml" | "yml" => Ok(Schema::YAML),
+            _ => Err(ConfigurationError::UnknownSchema(value)),
         }
-        panic!("invalid schema")
     }
 }
 impl ToString for Schema {
@@ -18,28 +26,8 @@ impl ToString for Schema {
         match self {
             Schema::JSON => String::from("json"),
             Schema::TOML => String::from("toml"),
+            Schema::JSONNET => String::from("jsonnet"),
+            Schema::YAML => String::from("yaml"),
         }
     }
 }
-
-#[derive(Clone, Eq, PartialEq, Hash)]
-pub struct ModuleKey {
-    pub module_name: String,
-}
-
-impl ModuleKey {
-    pub fn new(module_name: String) -> Self {
-        Self { module_name }
-    }
-}
-
-#[derive(Clone, Eq, PartialEq, Hash)]
-pub struct ModuleValue {
-    data: Vec<u8>,
-}
-
-impl ModuleValue {
-    pub fn new(data: Vec<u8>) -> Self {
-        Self { data }
-    }
-}
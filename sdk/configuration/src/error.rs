@@ -1,4 +1,6 @@
+use crate::validate::Violation;
 use jsonnet::Error;
+use std::path::{Path, PathBuf};
 pub use thiserror::Error;
 
 pub type ConfigurationResult<T> = Result<T, ConfigurationError>;
@@ -10,12 +12,93 @@ pub enum ConfigurationError {
     #[error("module not exist")]
     ModuleNotExists,
 
+    #[error("unknown schema:{0} ,expected json ,toml ,jsonnet or yaml")]
+    UnknownSchema(String),
+
+    #[error("configuration root {0:?} does not exist")]
+    RootNotExists(PathBuf),
+
+    #[error("read {path:?} failed:{source}")]
+    ReadError {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    // json_path is `.` when the document itself is malformed
+    #[error("parse {path:?} failed at {json_path} (line {line} ,column {column}):{message}")]
+    ParseError {
+        path: PathBuf,
+        json_path: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+
+    #[error("unknown config type {config_type:?} ,valid types:{valid:?}")]
+    UnknownConfigType {
+        config_type: String,
+        valid: Vec<String>,
+    },
+
+    #[error("config type {config_type:?} has an unknown parent {parent:?} ,valid types:{valid:?}")]
+    UnknownParentType {
+        config_type: String,
+        parent: String,
+        valid: Vec<String>,
+    },
+
+    #[error("config types inherit from each other:{}", .0.join(" -> "))]
+    InheritanceCycle(Vec<String>),
+
+    #[error("no file of module {module} exists ,looked for {candidates:?}")]
+    ModuleFileNotFound {
+        module: String,
+        candidates: Vec<PathBuf>,
+    },
+
+    #[error("module {module} is defined by repos of the same precedence:{repos:?}")]
+    ModuleConflict { module: String, repos: Vec<PathBuf> },
+
+    #[error("configuration is invalid:\n{}", .0.iter().map(|v| v.to_string()).collect::<Vec<String>>().join("\n"))]
+    ValidationFailed(Vec<Violation>),
+
+    #[error(
+        "plugin {name} is configured in root.json but not registered ,registered:{registered:?}"
+    )]
+    UnknownPlugin {
+        name: String,
+        registered: Vec<String>,
+    },
+
+    #[error("plugin {plugin} failed:{message}")]
+    PluginFailed { plugin: String, message: String },
+
+    #[error("module {module} does not match its type at {json_path}:{message}")]
+    InvalidModule {
+        module: String,
+        json_path: String,
+        message: String,
+    },
+
     #[error("serde error:{0}")]
     SerdeError(#[from] serde_json::Error),
 
     #[error("io error:{0}")]
     IoError(#[from] std::io::Error),
 
+    #[error("watch error:{0}")]
+    WatchError(#[from] notify::Error),
+
+    #[error("yaml error:{0}")]
+    YamlError(#[from] serde_yaml::Error),
+
+    #[error("toml error at line {line} ,column {column}:{message}")]
+    TomlError {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+
     #[error("{0}")]
     StringError(String),
 
@@ -29,3 +112,53 @@ impl<'a> From<jsonnet::Error<'a>> for ConfigurationError {
         ConfigurationError::StringError(err)
     }
 }
+
+impl ConfigurationError {
+    pub fn from_json(path: &Path, json_path: &str, e: &serde_json::Error) -> Self {
+        // serde_json 的信息里自带位置 ,去掉避免重复
+        let suffix = format!(" at line {} column {}", e.line(), e.column());
+        let message = e.to_string();
+        ConfigurationError::ParseError {
+            path: path.to_path_buf(),
+            json_path: String::from(json_path),
+            line: e.line(),
+            column: e.column(),
+            message: String::from(message.trim_end_matches(suffix.as_str())),
+        }
+    }
+
+    // attaches the file to parse errors of a module file
+    pub fn in_file(self, path: &Path) -> Self {
+        match self {
+            ConfigurationError::SerdeError(e) => Self::from_json(path, ".", &e),
+            ConfigurationError::YamlError(e) => {
+                let (line, column) = e.location().map_or((0, 0), |l| (l.line(), l.column()));
+                ConfigurationError::ParseError {
+                    path: path.to_path_buf(),
+                    json_path: String::from("."),
+                    line,
+                    column,
+                    message: e.to_string(),
+                }
+            }
+            ConfigurationError::TomlError {
+                line,
+                column,
+                message,
+            } => ConfigurationError::ParseError {
+                path: path.to_path_buf(),
+                json_path: String::from("."),
+                line,
+                column,
+                message,
+            },
+            ConfigurationError::AnyHowError(e) => {
+                ConfigurationError::StringError(format!("parse {:?} failed:{:#}", path, e))
+            }
+            ConfigurationError::StringError(e) => {
+                ConfigurationError::StringError(format!("parse {:?} failed:{}", path, e))
+            }
+            other => other,
+        }
+    }
+}
@@ -0,0 +1,181 @@
+// 排查用: 每个模块来自哪些文件 ,脱敏之后的最终值 ,以及两个 config type 之间的差异
+use crate::cfg::Configuration;
+use crate::error::ConfigurationResult;
+use crate::plugin::unwrap_sealed;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+pub const REDACTED: &'static str = "<redacted>";
+
+// keys containing one of these ,compared case-insensitively ,are redacted as well as sealed values
+const SENSITIVE_KEYS: [&'static str; 5] =
+    ["password", "secret", "token", "credential", "privatekey"];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModuleSource {
+    pub module: String,
+    pub schema: String,
+    pub repo: PathBuf,
+    // as declared in root.json
+    pub module_path: PathBuf,
+    // the files merged into the module ,parents first
+    pub files: Vec<PathBuf>,
+    // the first file found along the inheritance list
+    pub module_due_path: Option<PathBuf>,
+    // mounted repos that define the module too ,with lower precedence
+    pub shadowed: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Difference {
+    // `module.key[0].sub`
+    pub path: String,
+    pub left: Option<Value>,
+    pub right: Option<Value>,
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (&self.left, &self.right) {
+            (Some(l), Some(r)) => write!(f, "~ {}: {} -> {}", self.path, l, r),
+            (Some(l), None) => write!(f, "- {}: {}", self.path, l),
+            (None, Some(r)) => write!(f, "+ {}: {}", self.path, r),
+            (None, None) => write!(f, "  {}", self.path),
+        }
+    }
+}
+
+fn is_sensitive(key: &str) -> bool {
+    let key = key.to_ascii_lowercase();
+    SENSITIVE_KEYS.iter().any(|k| key.contains(k))
+}
+
+// `sealed` are the paths that held `ENC[...]` before they were decrypted
+pub fn redact(value: &mut Value, sealed: &[String]) {
+    redact_at(value, "", sealed)
+}
+
+fn redact_at(value: &mut Value, path: &str, sealed: &[String]) {
+    let is_sealed = |v: &Value| v.as_str().map_or(false, |s| unwrap_sealed(s).is_some());
+    if sealed.iter().any(|p| p == path) || is_sealed(value) {
+        *value = Value::String(String::from(REDACTED));
+        return;
+    }
+    match value {
+        Value::Array(a) => {
+            for (i, v) in a.iter_mut().enumerate() {
+                redact_at(v, format!("{}[{}]", path, i).as_str(), sealed);
+            }
+        }
+        Value::Object(m) => {
+            for (k, v) in m.iter_mut() {
+                if is_sensitive(k) && !v.is_object() && !v.is_array() {
+                    *v = Value::String(String::from(REDACTED));
+                    continue;
+                }
+                let p = if path.is_empty() {
+                    k.clone()
+                } else {
+                    format!("{}.{}", path, k)
+                };
+                redact_at(v, p.as_str(), sealed);
+            }
+        }
+        _ => {}
+    }
+}
+
+// every module of `cfg` ,redacted ,a module that fails to load is reported as an error
+pub fn effective(cfg: &Configuration) -> ConfigurationResult<BTreeMap<String, Value>> {
+    let mut ret = BTreeMap::new();
+    for name in cfg.module_names() {
+        let v = cfg.redacted_value(name.as_str())?;
+        ret.insert(name, v);
+    }
+    Ok(ret)
+}
+
+pub fn diff_values(path: &str, left: Option<&Value>, right: Option<&Value>) -> Vec<Difference> {
+    let mut ret = Vec::new();
+    diff_at(path, left, right, &mut ret);
+    ret
+}
+
+fn diff_at(path: &str, left: Option<&Value>, right: Option<&Value>, out: &mut Vec<Difference>) {
+    match (left, right) {
+        (Some(Value::Object(l)), Some(Value::Object(r))) => {
+            let keys: BTreeSet<&String> = l.keys().chain(r.keys()).collect();
+            for k in keys {
+                diff_at(format!("{}.{}", path, k).as_str(), l.get(k), r.get(k), out);
+            }
+        }
+        (Some(Value::Array(l)), Some(Value::Array(r))) => {
+            for i in 0..l.len().max(r.len()) {
+                diff_at(format!("{}[{}]", path, i).as_str(), l.get(i), r.get(i), out);
+            }
+        }
+        (l, r) if l != r => out.push(Difference {
+            path: String::from(path),
+            left: l.cloned(),
+            right: r.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+// both sides are redacted ,modules only one side has show up as added or removed
+pub fn diff(left: &Configuration, right: &Configuration) -> ConfigurationResult<Vec<Difference>> {
+    let left = effective(left)?;
+    let right = effective(right)?;
+    let modules: BTreeSet<&String> = left.keys().chain(right.keys()).collect();
+    let mut ret = Vec::new();
+    for m in modules {
+        ret.extend(diff_values(m.as_str(), left.get(m), right.get(m)));
+    }
+    Ok(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inspect::{diff, diff_values, redact, REDACTED};
+    use crate::manager::Manager;
+    use serde_json::json;
+
+    #[test]
+    fn test_redact() {
+        let mut v = json!({"auth":{"password":"p","user":"u"},"apiToken":"t","hosts":["a","ENC[x]"],"key":"plain"});
+        redact(&mut v, &[String::from("key")]);
+        assert_eq!(
+            v,
+            json!({"auth":{"password":REDACTED,"user":"u"},"apiToken":REDACTED,"hosts":["a",REDACTED],"key":REDACTED})
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let d = diff_values(
+            "server",
+            Some(&json!({"port":1,"nodes":[{"id":"a"}],"old":true})),
+            Some(&json!({"port":2,"nodes":[{"id":"a"},{"id":"b"}]})),
+        );
+        let d: Vec<String> = d.iter().map(|d| d.to_string()).collect();
+        assert_eq!(
+            d,
+            vec![
+                "+ server.nodes[1]: {\"id\":\"b\"}",
+                "- server.old: true",
+                "~ server.port: 1 -> 2",
+            ]
+        );
+
+        let test1 = Manager::new_with_init("./config", "test1").unwrap();
+        let test2 = Manager::new_with_init("./config", "test2").unwrap();
+        let d = diff(test1.get_configuration(), test2.get_configuration()).unwrap();
+        let d: Vec<String> = d.iter().map(|d| d.to_string()).collect();
+        assert_eq!(d, vec!["~ nacos.serverAddr: \"12345\" -> \"0\""]);
+    }
+}
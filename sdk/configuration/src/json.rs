@@ -14,7 +14,102 @@ impl JsonValue {
 
 impl<T: DeserializeOwned + Clone> ConfigValueTrait<T> for JsonValue {
     fn as_object(&self) -> ConfigurationResult<T> {
-        let ret = serde_json::from_slice::<T>(self.data.as_slice())?;
+        let data = strip_comments(self.data.as_slice());
+        let ret = serde_json::from_slice::<T>(data.as_slice())?;
         Ok(ret)
     }
 }
+
+// `//` 与 `/* */` 注释替换成空格 ,换行保留 ,报错时的行列号不变
+pub fn strip_comments(data: &[u8]) -> Vec<u8> {
+    let mut ret = Vec::with_capacity(data.len());
+    let mut i = 0;
+    let mut in_string = false;
+    while i < data.len() {
+        let c = data[i];
+        if in_string {
+            ret.push(c);
+            if c == b'\\' && i + 1 < data.len() {
+                ret.push(data[i + 1]);
+                i += 1;
+            } else if c == b'"' {
+                in_string = false;
+            }
+            i += 1;
+            continue;
+        }
+        match (c, data.get(i + 1)) {
+            (b'"', _) => {
+                in_string = true;
+                ret.push(c);
+                i += 1;
+            }
+            (b'/', Some(b'/')) => {
+                while i < data.len() && data[i] != b'\n' {
+                    ret.push(b' ');
+                    i += 1;
+                }
+            }
+            (b'/', Some(b'*')) => {
+                ret.extend_from_slice(b"  ");
+                i += 2;
+                while i < data.len() && !(data[i] == b'*' && data.get(i + 1) == Some(&b'/')) {
+                    ret.push(if data[i] == b'\n' { b'\n' } else { b' ' });
+                    i += 1;
+                }
+                if i < data.len() {
+                    ret.extend_from_slice(b"  ");
+                    i += 2;
+                }
+            }
+            _ => {
+                ret.push(c);
+                i += 1;
+            }
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::json::{strip_comments, JsonValue};
+    use crate::value::ConfigValueTrait;
+    use serde_json::json;
+
+    #[test]
+    fn test_strip_comments() {
+        let data = br#"/*
+ * header
+ */
+{
+  "url": "http://a/*b*/", // trailing
+  "q": "say \"//\""
+}"#;
+        let stripped = strip_comments(data);
+        assert_eq!(stripped.len(), data.len());
+        let v: serde_json::Value = serde_json::from_slice(stripped.as_slice()).unwrap();
+        assert_eq!(v, json!({"url":"http://a/*b*/","q":"say \"//\""}));
+    }
+
+    #[test]
+    fn test_comment_error_position() {
+        let data = b"// header\n{\n  /* a */ \"a\": 1,\n  \"b\": ?\n}";
+        let e = serde_json::from_slice::<serde_json::Value>(strip_comments(data).as_slice())
+            .unwrap_err();
+        assert_eq!((e.line(), e.column()), (4, 8));
+
+        // 没闭合的块注释吞掉剩余内容
+        let stripped = strip_comments(b"{\"a\":1} /* open");
+        let v: serde_json::Value = serde_json::from_slice(stripped.as_slice()).unwrap();
+        assert_eq!(v, json!({"a":1}));
+    }
+
+    #[test]
+    fn test_comment_fixture() {
+        // config/test1 的 nacos.json 带有块注释和行尾注释
+        let data = std::fs::read("./config/test1/env/shared/nacos.json").unwrap();
+        let v: serde_json::Value = JsonValue::new(data).as_object().unwrap();
+        assert_eq!(v, json!({"serverAddr":"12345"}));
+    }
+}
@@ -0,0 +1,28 @@
+use crate::error::ConfigurationResult;
+use jsonnet::JsonnetVm;
+use std::collections::HashMap;
+use std::path::Path;
+
+// ext vars available to every jsonnet module via std.extVar
+pub const EXT_CONFIG_TYPE: &'static str = "configType";
+pub const EXT_CONFIG_ROOT: &'static str = "configRoot";
+// the process environment as an object ,std.extVar("env").HOME
+pub const EXT_ENV: &'static str = "env";
+
+// the file path is passed along so that relative imports resolve next to the module
+pub fn evaluate(
+    file_path: &Path,
+    data: &str,
+    ext_vars: &HashMap<String, String>,
+) -> ConfigurationResult<Vec<u8>> {
+    let mut vm = JsonnetVm::new();
+    for (k, v) in ext_vars {
+        vm.ext_var(k.as_str(), v.as_str());
+    }
+    let env: HashMap<String, String> = std::env::vars().collect();
+    let env = serde_json::to_string(&env)?;
+    vm.ext_code(EXT_ENV, env.as_str());
+    let name = file_path.to_string_lossy();
+    let output = vm.evaluate_snippet(name.as_ref(), data)?;
+    Ok(output.as_str().as_bytes().to_vec())
+}
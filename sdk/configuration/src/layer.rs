@@ -0,0 +1,420 @@
+// 覆盖层,优先级从低到高: 代码里的默认值 < 配置文件 < 环境变量 < 命令行 --set
+use crate::error::{ConfigurationError, ConfigurationResult};
+use crate::merge::merge;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+// CELL__NACOS__SERVER_ADDR=10.0.0.1 => nacos.serverAddr
+pub const ENV_PREFIX: &'static str = "CELL";
+// CELL_JSON__NACOS__TIMEOUT=5 ,the value is json like `key:=json` ,for fields the files leave out
+pub const ENV_JSON_PREFIX: &'static str = "CELL_JSON";
+pub const ENV_SEPARATOR: &'static str = "__";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LayerKind {
+    Env,
+    Cli,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OverrideValue {
+    // 环境变量和 `key=value` ,按目标字段的类型转换
+    Raw(String),
+    // `key:=json` ,原样使用
+    Json(Value),
+}
+
+impl OverrideValue {
+    // "123456" 只有在目标字段是数字时才变成数字 ,否则保持字符串
+    fn resolve(&self, target: &Value) -> Value {
+        let raw = match self {
+            OverrideValue::Json(v) => return v.clone(),
+            OverrideValue::Raw(raw) => raw,
+        };
+        let parsed = match target {
+            Value::Number(_) | Value::Bool(_) | Value::Array(_) | Value::Object(_) => {
+                serde_json::from_str::<Value>(raw.trim()).ok()
+            }
+            _ => None,
+        };
+        match parsed {
+            Some(v) if same_kind(&v, target) => v,
+            _ => Value::String(raw.clone()),
+        }
+    }
+}
+
+fn same_kind(a: &Value, b: &Value) -> bool {
+    std::mem::discriminant(a) == std::mem::discriminant(b)
+}
+
+#[derive(Clone, Debug)]
+pub struct Override {
+    pub kind: LayerKind,
+    pub module: String,
+    pub path: Vec<String>,
+    pub value: OverrideValue,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Layers {
+    defaults: HashMap<String, Value>,
+    overrides: Vec<Override>,
+}
+
+impl Layers {
+    pub fn set_default<T: Serialize>(
+        &mut self,
+        module: &str,
+        value: &T,
+    ) -> ConfigurationResult<()> {
+        let value = serde_json::to_value(value)?;
+        match self.defaults.get_mut(module) {
+            Some(existing) => merge(existing, value),
+            None => {
+                self.defaults.insert(String::from(module), value);
+            }
+        }
+        Ok(())
+    }
+
+    // only variables starting with `CELL__` or `CELL_JSON__` are picked up ,
+    // `CELL__` values follow the type of the field they replace
+    pub fn load_env<I: IntoIterator<Item = (String, String)>>(
+        &mut self,
+        vars: I,
+    ) -> ConfigurationResult<()> {
+        let raw_prefix = format!("{}{}", ENV_PREFIX, ENV_SEPARATOR);
+        let json_prefix = format!("{}{}", ENV_JSON_PREFIX, ENV_SEPARATOR);
+        self.overrides.retain(|o| o.kind != LayerKind::Env);
+        let mut vars: Vec<(String, String)> = vars
+            .into_iter()
+            .filter(|(k, _)| {
+                k.starts_with(raw_prefix.as_str()) || k.starts_with(json_prefix.as_str())
+            })
+            .collect();
+        // 保证结果不依赖环境变量的遍历顺序
+        vars.sort();
+        for (k, v) in vars {
+            let (rest, value) = match k.strip_prefix(json_prefix.as_str()) {
+                Some(rest) => {
+                    let json = serde_json::from_str::<Value>(v.as_str()).map_err(|e| {
+                        ConfigurationError::StringError(format!("invalid json in {}:{}", k, e))
+                    })?;
+                    (rest, OverrideValue::Json(json))
+                }
+                None => (&k[raw_prefix.len()..], OverrideValue::Raw(v.clone())),
+            };
+            let mut parts: Vec<String> = rest.split(ENV_SEPARATOR).map(String::from).collect();
+            if parts.len() < 2 || parts.iter().any(|p| p.is_empty()) {
+                continue;
+            }
+            let module = parts.remove(0);
+            self.overrides.push(Override {
+                kind: LayerKind::Env,
+                module,
+                path: parts.iter().map(|p| camel_case(p)).collect(),
+                value,
+            });
+        }
+        Ok(())
+    }
+
+    // `module.key.sub=value` or `module.key.sub:=json` ,later entries win
+    pub fn add_sets(&mut self, sets: &[String]) -> ConfigurationResult<()> {
+        let mut parsed = Vec::new();
+        for s in sets {
+            parsed.push(parse_set(s.as_str())?);
+        }
+        self.overrides.extend(parsed);
+        Ok(())
+    }
+
+    pub fn has_module(&self, module: &str) -> bool {
+        self.defaults.contains_key(module)
+    }
+
+    pub fn apply(&self, module: &str, file_value: Option<Value>) -> ConfigurationResult<Value> {
+        let mut ret = self.defaults.get(module).cloned().unwrap_or(Value::Null);
+        if let Some(v) = file_value {
+            merge(&mut ret, v);
+        }
+        let mut overrides: Vec<&Override> = self
+            .overrides
+            .iter()
+            .filter(|o| normalize(o.module.as_str()) == normalize(module))
+            .collect();
+        overrides.sort_by_key(|o| o.kind);
+        for o in overrides {
+            set_path(&mut ret, o.path.as_slice(), &o.value).map_err(|e| {
+                ConfigurationError::StringError(format!(
+                    "override {}.{} failed:{}",
+                    o.module,
+                    o.path.join("."),
+                    e
+                ))
+            })?;
+        }
+        Ok(ret)
+    }
+}
+
+pub fn parse_set(s: &str) -> ConfigurationResult<Override> {
+    let invalid = || {
+        ConfigurationError::StringError(format!(
+            "invalid override {:?} ,expected module.key=value or module.key:=json",
+            s
+        ))
+    };
+    let (key, value) = s.split_once('=').ok_or_else(invalid)?;
+    let (key, value) = match key.strip_suffix(':') {
+        Some(key) => {
+            let json = serde_json::from_str::<Value>(value).map_err(|e| {
+                ConfigurationError::StringError(format!("invalid json in override {:?}:{}", s, e))
+            })?;
+            (key, OverrideValue::Json(json))
+        }
+        None => (key, OverrideValue::Raw(String::from(value))),
+    };
+    let mut parts: Vec<String> = key.trim().split('.').map(String::from).collect();
+    if parts.len() < 2 || parts.iter().any(|p| p.is_empty()) {
+        return Err(invalid());
+    }
+    let module = parts.remove(0);
+    Ok(Override {
+        kind: LayerKind::Cli,
+        module,
+        path: parts,
+        value,
+    })
+}
+
+// SERVER_ADDR => serverAddr
+fn camel_case(s: &str) -> String {
+    let mut ret = String::new();
+    for (i, part) in s.split('_').filter(|p| !p.is_empty()).enumerate() {
+        let lower = part.to_ascii_lowercase();
+        if i == 0 {
+            ret.push_str(lower.as_str());
+            continue;
+        }
+        let mut chars = lower.chars();
+        if let Some(c) = chars.next() {
+            ret.push(c.to_ascii_uppercase());
+            ret.extend(chars);
+        }
+    }
+    ret
+}
+
+// serverAddr ,SERVER_ADDR ,server-addr 视为同一个 key
+fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(|c| *c != '_' && *c != '-')
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+// 数组只能按下标改 ,下标等于长度时追加 ,不会把整个数组换成 object
+fn set_path(target: &mut Value, path: &[String], value: &OverrideValue) -> Result<(), String> {
+    let (head, rest) = match path.split_first() {
+        Some(v) => v,
+        None => {
+            *target = value.resolve(target);
+            return Ok(());
+        }
+    };
+    if let Value::Array(arr) = target {
+        let index = head
+            .parse::<usize>()
+            .map_err(|_| format!("{:?} is not an array index", head))?;
+        if index == arr.len() {
+            arr.push(Value::Null);
+        }
+        let len = arr.len();
+        return match arr.get_mut(index) {
+            Some(item) => set_path(item, rest, value),
+            None => Err(format!(
+                "index {} out of range ,the array has {} items",
+                index, len
+            )),
+        };
+    }
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let obj = target.as_object_mut().unwrap();
+    let key = obj
+        .keys()
+        .find(|k| normalize(k) == normalize(head))
+        .cloned()
+        .unwrap_or_else(|| head.clone());
+    let child = obj.entry(key).or_insert(Value::Null);
+    set_path(child, rest, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::layer::{camel_case, parse_set, Layers, OverrideValue};
+    use serde_json::json;
+
+    fn vars(v: &[(&str, &str)]) -> Vec<(String, String)> {
+        v.iter()
+            .map(|(k, v)| (String::from(*k), String::from(*v)))
+            .collect()
+    }
+
+    #[test]
+    fn test_precedence() {
+        let mut layers = Layers::default();
+        layers
+            .set_default(
+                "nacos",
+                &json!({"serverAddr":"default","port":8848,"timeout":3}),
+            )
+            .unwrap();
+        layers
+            .load_env(vars(&[
+                ("CELL__NACOS__SERVER_ADDR", "env"),
+                ("CELL__NACOS__PORT", "9000"),
+                ("CELL__NACOS__AUTH__USER_NAME", "nori"),
+                ("OTHER__NACOS__PORT", "1"),
+            ]))
+            .unwrap();
+        layers
+            .add_sets(&[String::from("nacos.serverAddr=cli")])
+            .unwrap();
+
+        let v = layers
+            .apply("nacos", Some(json!({"serverAddr":"file","port":1})))
+            .unwrap();
+        assert_eq!(
+            v,
+            json!({"serverAddr":"cli","port":9000,"timeout":3,"auth":{"userName":"nori"}})
+        );
+        assert_eq!(layers.apply("other", None).unwrap(), json!(null));
+    }
+
+    #[test]
+    fn test_parse_set() {
+        let o = parse_set("server.nodes.0.nodeId=gateway").unwrap();
+        assert_eq!(o.module, "server");
+        assert_eq!(o.path, vec!["nodes", "0", "nodeId"]);
+        assert!(parse_set("server").is_err());
+        assert!(parse_set("server=1").is_err());
+        assert!(parse_set("server..a=1").is_err());
+
+        let mut layers = Layers::default();
+        layers
+            .add_sets(&[String::from("server.nodes.0.nodeId=gateway")])
+            .unwrap();
+        let v = layers
+            .apply(
+                "server",
+                Some(json!({"nodes":[{"nodeId":"a"},{"nodeId":"b"}]})),
+            )
+            .unwrap();
+        assert_eq!(v, json!({"nodes":[{"nodeId":"gateway"},{"nodeId":"b"}]}));
+
+        let o = parse_set("server.ports:=[80,443]").unwrap();
+        assert_eq!(o.path, vec!["ports"]);
+        assert_eq!(o.value, OverrideValue::Json(json!([80, 443])));
+        assert!(parse_set("server.ports:=[80,").is_err());
+
+        assert_eq!(camel_case("SERVER_ADDR"), "serverAddr");
+        assert_eq!(camel_case("PORT"), "port");
+    }
+
+    #[test]
+    fn test_string_values() {
+        let mut layers = Layers::default();
+        layers
+            .load_env(vars(&[
+                ("CELL__NACOS__PASSWORD", "123456"),
+                ("CELL__NACOS__ENABLED", "false"),
+                ("CELL__NACOS__NAME", "null"),
+                ("CELL__NACOS__PORT", "9000"),
+                ("CELL__NACOS__TAGS", "[\"a\"]"),
+                ("CELL__NACOS__TIMEOUT", "soon"),
+            ]))
+            .unwrap();
+        layers
+            .add_sets(&[
+                String::from("nacos.token=true"),
+                String::from("nacos.retry:=3"),
+            ])
+            .unwrap();
+        let v = layers
+            .apply(
+                "nacos",
+                Some(json!({
+                    "password":"x",
+                    "enabled":true,
+                    "name":"n",
+                    "port":1,
+                    "tags":[],
+                    "timeout":3,
+                    "token":"t"
+                })),
+            )
+            .unwrap();
+        assert_eq!(
+            v,
+            json!({
+                "password":"123456",
+                "enabled":false,
+                "name":"null",
+                "port":9000,
+                "tags":["a"],
+                "timeout":"soon",
+                "token":"true",
+                "retry":3
+            })
+        );
+    }
+
+    #[test]
+    fn test_array_index() {
+        let nodes = json!({"nodes":[{"nodeId":"a"}]});
+        let mut layers = Layers::default();
+        layers
+            .add_sets(&[String::from("server.nodes.1.nodeId=b")])
+            .unwrap();
+        let v = layers.apply("server", Some(nodes.clone())).unwrap();
+        assert_eq!(v, json!({"nodes":[{"nodeId":"a"},{"nodeId":"b"}]}));
+
+        // 越界或者不是下标都报错 ,不能把数组整个换掉
+        for set in ["server.nodes.2.nodeId=c", "server.nodes.first.nodeId=c"] {
+            let mut layers = Layers::default();
+            layers.add_sets(&[String::from(set)]).unwrap();
+            assert!(layers.apply("server", Some(nodes.clone())).is_err());
+        }
+    }
+
+    #[test]
+    fn test_json_env() {
+        let mut layers = Layers::default();
+        layers
+            .load_env(vars(&[
+                ("CELL__NACOS__PASSWORD", "123456"),
+                ("CELL_JSON__NACOS__TIMEOUT", "5"),
+                ("CELL_JSON__NACOS__TAGS", "[\"a\"]"),
+            ]))
+            .unwrap();
+        // none of the fields are in the file
+        let v = layers.apply("nacos", Some(json!({}))).unwrap();
+        assert_eq!(v, json!({"password":"123456","timeout":5,"tags":["a"]}));
+
+        #[derive(serde::Deserialize)]
+        struct Nacos {
+            timeout: Option<u64>,
+        }
+        let n: Nacos = serde_json::from_value(v).unwrap();
+        assert_eq!(n.timeout, Some(5));
+
+        assert!(layers
+            .load_env(vars(&[("CELL_JSON__NACOS__TIMEOUT", "soon")]))
+            .is_err());
+    }
+}
@@ -1,8 +1,27 @@
+#[macro_use]
+extern crate logsdk;
+
+use logsdk::common::LogLevel;
+use logsdk::module::CellModule;
+
 pub mod cfg;
 mod enums;
 pub mod error;
+pub mod inspect;
 pub mod json;
+pub mod jsonnet;
+pub mod layer;
+pub mod logger;
 pub mod manager;
+pub mod merge;
 pub mod parser;
+pub mod plugin;
+pub mod seal;
 pub mod toml;
+pub mod validate;
 pub mod value;
+pub mod watch;
+pub mod yaml;
+
+pub(crate) static CONFIGURATION: &CellModule =
+    &CellModule::new(1, "CONFIGURATION", &LogLevel::Info);
@@ -0,0 +1,343 @@
+use crate::cfg::Configuration;
+use crate::error::{ConfigurationError, ConfigurationResult};
+use crate::watch::ConfigWatcher;
+use crate::CONFIGURATION;
+use logsdk::common::{LogFormat, LogLevel};
+use logsdk::file::{setup_file_appenders, FileAppenderProperty};
+use logsdk::writer::{
+    shutdown_async_writer, start_async_writer, AsyncWriterProperty, OverflowPolicy,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// logger 模块的配置,例如:{"level":"info","format":"json","modules":{"DISPATCHER":"debug"}}
+pub const LOGGER_MODULE: &'static str = "logger";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LoggerSettings {
+    #[serde(default)]
+    pub level: Option<String>,
+    // keyed by CellModule name
+    #[serde(default)]
+    pub modules: HashMap<String, String>,
+    // console | json
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub files: Vec<FileAppenderSettings>,
+    // attach a backtrace to Error level entries
+    #[serde(default)]
+    pub backtrace: bool,
+    // forward logsdk entries into tracing
+    #[serde(default)]
+    pub tracing: bool,
+    // absent means logs are written on the calling thread
+    #[serde(default, rename = "async")]
+    pub async_writer: Option<AsyncWriterSettings>,
+}
+
+// {"capacity":8192,"overflow":"drop"}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AsyncWriterSettings {
+    #[serde(default = "default_capacity")]
+    pub capacity: usize,
+    // block | drop
+    #[serde(default)]
+    pub overflow: Option<String>,
+}
+
+fn default_capacity() -> usize {
+    AsyncWriterProperty::default().capacity
+}
+
+impl AsyncWriterSettings {
+    pub fn resolve(&self) -> ConfigurationResult<AsyncWriterProperty> {
+        let overflow = match self.overflow.as_deref() {
+            None | Some("block") => OverflowPolicy::Block,
+            Some("drop") => OverflowPolicy::Drop,
+            Some(v) => {
+                return Err(ConfigurationError::StringError(format!(
+                    "unknown overflow policy:{}",
+                    v
+                )))
+            }
+        };
+        Ok(AsyncWriterProperty::default()
+            .with_capacity(self.capacity)
+            .with_overflow(overflow))
+    }
+}
+
+// {"path":"logs/http.log","modules":["HTTP_*"],"maxSize":10485760,"daily":true,"retention":7,"compress":true}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileAppenderSettings {
+    pub path: String,
+    #[serde(default)]
+    pub modules: Vec<String>,
+    #[serde(default)]
+    pub max_size: Option<u64>,
+    #[serde(default)]
+    pub daily: bool,
+    #[serde(default = "default_retention")]
+    pub retention: usize,
+    #[serde(default)]
+    pub compress: bool,
+}
+
+fn default_retention() -> usize {
+    7
+}
+
+impl From<&FileAppenderSettings> for FileAppenderProperty {
+    fn from(s: &FileAppenderSettings) -> Self {
+        let mut ret = FileAppenderProperty::new(s.path.as_str())
+            .with_modules(s.modules.clone())
+            .with_daily(s.daily)
+            .with_retention(s.retention)
+            .with_compress(s.compress);
+        if let Some(max) = s.max_size {
+            ret = ret.with_max_size(max);
+        }
+        ret
+    }
+}
+
+impl LoggerSettings {
+    pub fn resolve(&self) -> ConfigurationResult<(LogLevel, HashMap<String, LogLevel>)> {
+        let global = match &self.level {
+            Some(l) => parse_level(l)?,
+            None => LogLevel::Info,
+        };
+        let mut modules = HashMap::new();
+        for (k, v) in &self.modules {
+            modules.insert(k.clone(), parse_level(v)?);
+        }
+        Ok((global, modules))
+    }
+
+    pub fn resolve_format(&self) -> ConfigurationResult<Option<LogFormat>> {
+        match &self.format {
+            Some(f) => f
+                .parse::<LogFormat>()
+                .map(Some)
+                .map_err(|e| ConfigurationError::StringError(e.to_string())),
+            None => Ok(None),
+        }
+    }
+}
+
+fn parse_level(l: &str) -> ConfigurationResult<LogLevel> {
+    l.parse::<LogLevel>()
+        .map_err(|e| ConfigurationError::StringError(e.to_string()))
+}
+
+// 全部校验通过之后才替换 ,不会出现只生效一半的情况
+pub fn apply_logger_settings(settings: &LoggerSettings) -> ConfigurationResult<()> {
+    let (global, modules) = settings.resolve()?;
+    let format = settings.resolve_format()?;
+    let async_writer = match &settings.async_writer {
+        Some(a) => Some(a.resolve()?),
+        None => None,
+    };
+    setup_file_appenders(
+        settings
+            .files
+            .iter()
+            .map(FileAppenderProperty::from)
+            .collect(),
+    )?;
+    logsdk::update_logger_configuration(|c| {
+        c.global_loglevel = global;
+        c.module_loglevels = modules.clone();
+        c.error_backtrace = settings.backtrace;
+        c.forward_to_tracing = settings.tracing;
+        if let Some(f) = format {
+            c.format = f;
+        }
+    });
+    match async_writer {
+        Some(p) => start_async_writer(p),
+        None => shutdown_async_writer(),
+    }
+    Ok(())
+}
+
+// no-op when the repo does not declare a logger module
+pub fn reload_logger(cfg: &Configuration) -> ConfigurationResult<()> {
+    match cfg.get_config::<LoggerSettings>(LOGGER_MODULE) {
+        Ok(settings) => apply_logger_settings(&settings),
+        Err(ConfigurationError::ModuleNotExists) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+// 热更新 logger 模块 ,校验失败时保留原来的配置
+pub fn watch_logger(watcher: &ConfigWatcher) {
+    watcher.on_change(|module, value| {
+        if module != LOGGER_MODULE {
+            return;
+        }
+        if let Err(e) = reload_logger_from(value) {
+            cerror!(CONFIGURATION, "reload logger failed:{}", e);
+        }
+    });
+}
+
+fn reload_logger_from(value: Arc<serde_json::Value>) -> ConfigurationResult<()> {
+    let settings = LoggerSettings::deserialize(value.as_ref())?;
+    apply_logger_settings(&settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::logger::{
+        apply_logger_settings, reload_logger, watch_logger, AsyncWriterSettings, LoggerSettings,
+    };
+    use crate::manager::Manager;
+    use crate::watch::{WatchMode, WatchOptions};
+    use logsdk::common::{LogFormat, LogLevel};
+    use logsdk::file::{flush_files, setup_file_appenders, FileAppenderProperty};
+    use logsdk::log::CellLoggerConfiguration;
+    use logsdk::module::CellModule;
+    use logsdk::writer::{shutdown_async_writer, AsyncWriterProperty, OverflowPolicy};
+    use logsdk::{logger_configuration, setup_logger_configuration};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::{Mutex, MutexGuard};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    // the tests below change the process wide logger ,hold this and call restore at the end
+    fn logger_lock() -> MutexGuard<'static, ()> {
+        static LOCK: Mutex<()> = Mutex::new(());
+        LOCK.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn restore(saved: &CellLoggerConfiguration) {
+        setup_logger_configuration(saved.clone());
+        setup_file_appenders(vec![]).unwrap();
+        shutdown_async_writer();
+    }
+
+    fn temp_repo(name: &str, logger: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("configuration-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("Default")).unwrap();
+        fs::write(
+            dir.join("root.json"),
+            r#"{"types":{"Default":{"parent":null}},"defaultType":"Default","configs":[{"modules":{"logger":"logger.json"},"schema":null}],"plugins":{}}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("Default/logger.json"), logger).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_apply_logger_settings() {
+        let _lock = logger_lock();
+        let saved = logger_configuration();
+        let settings: LoggerSettings = serde_json::from_str(
+            r#"{"level":"warn","format":"json","modules":{"DISPATCHER":"debug"}}"#,
+        )
+        .unwrap();
+        apply_logger_settings(&settings).unwrap();
+        let cfg = logger_configuration();
+        assert_eq!(cfg.global_loglevel, LogLevel::Warn);
+        assert_eq!(cfg.module_loglevels["DISPATCHER"], LogLevel::Debug);
+        assert_eq!(cfg.format, LogFormat::Json);
+
+        let bad: LoggerSettings =
+            serde_json::from_str(r#"{"level":"info","modules":{"DISPATCHER":"loud"}}"#).unwrap();
+        assert!(apply_logger_settings(&bad).is_err());
+        assert_eq!(logger_configuration().global_loglevel, LogLevel::Warn);
+        restore(&saved);
+    }
+
+    #[test]
+    fn test_reload_logger() {
+        let _lock = logger_lock();
+        let saved = logger_configuration();
+        let dir = temp_repo("logger", r#"{"level":"error","modules":{"HTTP":"trace"}}"#);
+        let manager = Manager::new_with_init(&dir, "Default").unwrap();
+        reload_logger(manager.get_configuration()).unwrap();
+        let cfg = logger_configuration();
+        assert_eq!(cfg.global_loglevel, LogLevel::Error);
+        assert_eq!(cfg.module_loglevels["HTTP"], LogLevel::Trace);
+
+        let options = WatchOptions::default()
+            .with_mode(WatchMode::Poll(Duration::from_millis(20)))
+            .with_debounce(Duration::from_millis(20));
+        let watcher = manager.watch(options).unwrap();
+        watch_logger(&watcher);
+        // 让 poll watcher 先记下文件的初始状态
+        thread::sleep(Duration::from_millis(100));
+        fs::write(dir.join("Default/logger.json"), r#"{"level":"debug"}"#).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while logger_configuration().global_loglevel != LogLevel::Debug {
+            assert!(Instant::now() < deadline, "logger was not reloaded");
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(logger_configuration().module_loglevels.is_empty());
+        watcher.stop();
+
+        // a repo without a logger module leaves the logger alone
+        let m = Manager::new_with_init("./config", "Default").unwrap();
+        reload_logger(m.get_configuration()).unwrap();
+        assert_eq!(logger_configuration().global_loglevel, LogLevel::Debug);
+        restore(&saved);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_settings() {
+        let settings: LoggerSettings = serde_json::from_str(
+            r#"{"files":[{"path":"logs/http.log","modules":["HTTP_*"],"maxSize":1024,"compress":true}]}"#,
+        )
+        .unwrap();
+        let p = FileAppenderProperty::from(&settings.files[0]);
+        assert_eq!(p.max_size, Some(1024));
+        assert_eq!(p.retention, 7);
+        assert!(p.compress && !p.daily);
+        assert!(p.accept("HTTP_DISPATCH"));
+    }
+
+    #[test]
+    fn test_reload_files() {
+        static FILES: &CellModule = &CellModule::new(1, "LOGGER_FILES", &LogLevel::Info);
+        static OTHER: &CellModule = &CellModule::new(2, "LOGGER_OTHER", &LogLevel::Info);
+        let _lock = logger_lock();
+        let saved = logger_configuration();
+        let dir = std::env::temp_dir().join(format!("configuration-files-{}", std::process::id()));
+        let path = dir.join("logs/files.log");
+        let logger = serde_json::json!({
+            "files": [{"path": path.to_str().unwrap(), "modules": ["LOGGER_FILES"]}]
+        });
+        let dir = temp_repo("files", logger.to_string().as_str());
+        let manager = Manager::new_with_init(&dir, "Default").unwrap();
+        reload_logger(manager.get_configuration()).unwrap();
+
+        cerror!(FILES, "written to the file");
+        cerror!(OTHER, "filtered out");
+        flush_files();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("written to the file"));
+        assert!(!content.contains("filtered out"));
+        restore(&saved);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_async_settings() {
+        let settings: LoggerSettings =
+            serde_json::from_str(r#"{"async":{"overflow":"drop"}}"#).unwrap();
+        let p = settings.async_writer.unwrap().resolve().unwrap();
+        assert_eq!(p.overflow, OverflowPolicy::Drop);
+        assert_eq!(p.capacity, AsyncWriterProperty::default().capacity);
+
+        let bad: AsyncWriterSettings = serde_json::from_str(r#"{"overflow":"spill"}"#).unwrap();
+        assert!(bad.resolve().is_err());
+    }
+}
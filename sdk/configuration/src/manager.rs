@@ -1,5 +1,7 @@
-use crate::cfg::Configuration;
-use crate::error::ConfigurationResult;
+use crate::cfg::{Configuration, RootConfig};
+use crate::error::{ConfigurationError, ConfigurationResult};
+use crate::plugin::{FileKvPlugin, TemplatePlugin};
+use crate::watch::{ConfigWatcher, WatchOptions};
 use std::path::{Path, PathBuf};
 
 pub struct Manager {
@@ -10,23 +12,52 @@ pub struct Manager {
 }
 
 impl Manager {
-    pub fn new_with_init<P: AsRef<Path>>(root_path: P, config_type: &str) -> Self {
-        let mut m = Self::new(root_path, config_type);
-        m.initialize().unwrap();
-        m
+    pub fn new_with_init<P: AsRef<Path>>(
+        root_path: P,
+        config_type: &str,
+    ) -> ConfigurationResult<Self> {
+        let mut m = Self::new(root_path, config_type)?;
+        m.initialize()?;
+        Ok(m)
     }
 
-    pub fn new<P: AsRef<Path>>(root_path: P, config_type: &str) -> Self {
+    pub fn new<P: AsRef<Path>>(root_path: P, config_type: &str) -> ConfigurationResult<Self> {
         let root_path = root_path.as_ref().to_path_buf();
-        if !root_path.exists() {
-            panic!("configuration is not exist")
+        if !root_path.is_dir() {
+            return Err(ConfigurationError::RootNotExists(root_path));
         }
         let cfg = Configuration::new(root_path.clone(), config_type);
-        Self {
+        Ok(Self {
             root_path: root_path.clone(),
             config_type: config_type.to_string(),
             current_configuration: cfg,
-        }
+        })
+    }
+
+    // the `defaultType` of root.json ,for when no config type is given
+    pub fn default_type<P: AsRef<Path>>(root_path: P) -> ConfigurationResult<String> {
+        let root = RootConfig::load(&root_path.as_ref().join("root.json"))?;
+        Ok(String::from(root.get_default_type()))
+    }
+
+    // set up the way a service loads it: the builtin plugins registered and `sets` applied ,
+    // the config type defaults to the defaultType of root.json ,initialize is left to the caller
+    pub fn prepare<P: AsRef<Path>>(
+        root_path: P,
+        config_type: Option<&str>,
+        sets: &[String],
+    ) -> ConfigurationResult<Self> {
+        let config_type = match config_type {
+            Some(t) => String::from(t),
+            None => Self::default_type(root_path.as_ref())?,
+        };
+        let mut m = Self::new(root_path, config_type.as_str())?;
+        let cfg = m.get_configuration_mut();
+        // builtin plugins stay idle unless root.json has settings for them
+        cfg.register_plugin(FileKvPlugin::default());
+        cfg.register_plugin(TemplatePlugin::default());
+        cfg.add_overrides(sets)?;
+        Ok(m)
     }
 
     pub fn initialize(&mut self) -> ConfigurationResult<()> {
@@ -39,13 +70,25 @@ impl Manager {
     pub fn get_configuration_mut(&mut self) -> &mut Configuration {
         &mut self.current_configuration
     }
+
+    // reloads changed module files in the background ,root.json changes need a restart
+    pub fn watch(&self, options: WatchOptions) -> ConfigurationResult<ConfigWatcher> {
+        ConfigWatcher::start(self.current_configuration.clone(), options)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::cfg::{Configuration, RootConfig};
+    use crate::error::{ConfigurationError, ConfigurationResult};
     use crate::manager::Manager;
+    use crate::plugin::{Decryptor, FileKvPlugin, SecretPlugin, TemplatePlugin};
+    use crate::seal::SealKey;
+    use crate::validate::ModuleValidator;
     use serde::{Deserialize, Serialize};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::Arc;
 
     #[derive(Debug, Clone, Serialize, Deserialize)]
     #[serde(rename_all = "camelCase")]
@@ -54,7 +97,7 @@ mod tests {
     }
     #[test]
     pub fn test_toml() {
-        let manager = Manager::new_with_init("./config_toml", "Default");
+        let manager = Manager::new_with_init("./config_toml", "Default").unwrap();
         let nacos = manager
             .get_configuration()
             .get_config::<Nacos>("nacos")
@@ -69,7 +112,7 @@ mod tests {
             pub test: String,
         }
 
-        let manager = Manager::new_with_init("./config", "test2");
+        let manager = Manager::new_with_init("./config", "test2").unwrap();
 
         let nacos = manager
             .get_configuration()
@@ -83,4 +126,474 @@ mod tests {
             .unwrap();
         println!("{:?}", test);
     }
+
+    #[test]
+    fn test_inheritance_merge() {
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct NacosNamespace {
+            pub server_addr: String,
+            pub namespace: String,
+        }
+
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        pub struct Redis {
+            pub addr: String,
+            pub db: u32,
+        }
+
+        // child only overrides serverAddr ,namespace comes from Default
+        let manager = Manager::new_with_init("./config_merge", "child").unwrap();
+        let nacos = manager
+            .get_configuration()
+            .get_config::<NacosNamespace>("nacos")
+            .unwrap();
+        assert_eq!(nacos.server_addr, "child");
+        assert_eq!(nacos.namespace, "public");
+        let redis = manager
+            .get_configuration()
+            .get_config::<Redis>("redis")
+            .unwrap();
+        assert_eq!(redis.addr, "127.0.0.1:6379");
+        assert_eq!(redis.db, 1);
+
+        let manager = Manager::new_with_init("./config_merge", "Default").unwrap();
+        let nacos = manager
+            .get_configuration()
+            .get_config::<NacosNamespace>("nacos")
+            .unwrap();
+        assert_eq!(nacos.server_addr, "127.0.0.1");
+    }
+
+    #[test]
+    fn test_jsonnet_yaml() {
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        pub struct Server {
+            pub name: String,
+            pub port: u16,
+        }
+
+        let manager = Manager::new_with_init("./config_mixed", "test1").unwrap();
+        let nacos = manager
+            .get_configuration()
+            .get_config::<Nacos>("nacos")
+            .unwrap();
+        assert_eq!(nacos.server_addr, "test1");
+        let server = manager
+            .get_configuration()
+            .get_config::<Server>("server")
+            .unwrap();
+        assert_eq!(server.name, "gateway");
+        assert_eq!(server.port, 9011);
+    }
+
+    #[test]
+    fn test_unknown_schema() {
+        let root: RootConfig = serde_json::from_str(
+            r#"{"types":{"Default":{"parent":null}},"defaultType":"Default","configs":[{"modules":{"a":"a.xml"},"schema":"xml"}],"plugins":{}}"#,
+        )
+        .unwrap();
+        match root.get_modules() {
+            Err(ConfigurationError::UnknownSchema(s)) => assert_eq!(s, "xml"),
+            _ => panic!("expected UnknownSchema"),
+        }
+    }
+
+    #[test]
+    fn test_layers() {
+        #[derive(Debug, Clone, Serialize, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct Server {
+            pub server_addr: String,
+            pub port: u16,
+        }
+
+        let mut manager = Manager::new_with_init("./config", "test2").unwrap();
+        let cfg = manager.get_configuration_mut();
+        cfg.set_default(
+            "nacos",
+            &serde_json::json!({"serverAddr":"default","port":8848}),
+        )
+        .unwrap();
+        cfg.set_default("extra", &serde_json::json!({"serverAddr":"x","port":1}))
+            .unwrap();
+        cfg.add_overrides(&[String::from("nacos.port=9000")])
+            .unwrap();
+
+        let nacos = cfg.get_config::<Server>("nacos").unwrap();
+        assert_eq!(nacos.server_addr, "0");
+        assert_eq!(nacos.port, 9000);
+        // only declared through defaults
+        assert_eq!(cfg.get_config::<Server>("extra").unwrap().port, 1);
+        assert!(cfg.get_config::<Server>("missing").is_err());
+    }
+
+    fn temp_repo(name: &str, root: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("configuration-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("Default")).unwrap();
+        fs::write(dir.join("root.json"), root).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_init_errors() {
+        match Manager::new("./not_exists", "Default") {
+            Err(ConfigurationError::RootNotExists(p)) => {
+                assert_eq!(p, PathBuf::from("./not_exists"))
+            }
+            _ => panic!("expected RootNotExists"),
+        }
+
+        match Manager::new_with_init("./config", "test9") {
+            Err(ConfigurationError::UnknownConfigType { config_type, valid }) => {
+                assert_eq!(config_type, "test9");
+                assert_eq!(valid, vec!["Default", "test1", "test2"]);
+            }
+            _ => panic!("expected UnknownConfigType"),
+        }
+
+        let dir = temp_repo(
+            "bad-root",
+            "{\n  \"types\": {\"Default\": {\"parent\": 1}}\n}",
+        );
+        match Manager::new_with_init(&dir, "Default") {
+            Err(ConfigurationError::ParseError {
+                path,
+                json_path,
+                line,
+                ..
+            }) => {
+                assert_eq!(path, dir.join("root.json"));
+                assert_eq!(json_path, "types.Default.parent");
+                assert_eq!(line, 2);
+            }
+            _ => panic!("expected ParseError"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+
+        let dir = temp_repo(
+            "cycle",
+            r#"{"types":{"Default":{"parent":null},"a":{"parent":"c"},"b":{"parent":"a"},"c":{"parent":"b"}},"defaultType":"Default","configs":[],"plugins":{}}"#,
+        );
+        match Manager::new_with_init(&dir, "a") {
+            Err(ConfigurationError::InheritanceCycle(cycle)) => {
+                assert_eq!(cycle, vec!["a", "c", "b", "a"])
+            }
+            _ => panic!("expected InheritanceCycle"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_module_errors() {
+        #[derive(Debug, Clone, Deserialize)]
+        pub struct Node {
+            #[allow(dead_code)]
+            pub port: u16,
+        }
+        #[derive(Debug, Clone, Deserialize)]
+        pub struct Cluster {
+            #[allow(dead_code)]
+            pub nodes: Vec<Node>,
+        }
+
+        let dir = temp_repo(
+            "module",
+            r#"{"types":{"Default":{"parent":null}},"defaultType":"Default","configs":[{"modules":{"cluster":"cluster.json","broken":"broken.json","missing":"missing.json"},"schema":null},{"modules":{"brokenToml":"broken.toml"},"schema":"toml"}],"plugins":{}}"#,
+        );
+        fs::write(
+            dir.join("Default/cluster.json"),
+            r#"{"nodes":[{"port":1},{"port":"x"}]}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("Default/broken.json"), "{\n  \"a\": ,\n}").unwrap();
+        fs::write(dir.join("Default/broken.toml"), "a = 1\nb = = 2\n").unwrap();
+        let manager = Manager::new_with_init(&dir, "Default").unwrap();
+        let cfg = manager.get_configuration();
+
+        match cfg.get_config::<Cluster>("cluster") {
+            Err(ConfigurationError::InvalidModule {
+                module, json_path, ..
+            }) => {
+                assert_eq!(module, "cluster");
+                assert_eq!(json_path, "nodes[1].port");
+            }
+            _ => panic!("expected InvalidModule"),
+        }
+        match cfg.get_config::<serde_json::Value>("broken") {
+            Err(ConfigurationError::ParseError { path, line, .. }) => {
+                assert!(path.ends_with("Default/broken.json"));
+                assert_eq!(line, 2);
+            }
+            _ => panic!("expected ParseError"),
+        }
+        match cfg.get_config::<serde_json::Value>("brokenToml") {
+            Err(ConfigurationError::ParseError {
+                path, line, column, ..
+            }) => {
+                assert!(path.ends_with("Default/broken.toml"));
+                assert_eq!((line, column), (2, 5));
+            }
+            other => panic!("expected ParseError ,got {:?}", other.err()),
+        }
+        match cfg.get_config::<serde_json::Value>("missing") {
+            Err(ConfigurationError::ModuleFileNotFound { module, candidates }) => {
+                assert_eq!(module, "missing");
+                assert_eq!(candidates.len(), 1);
+            }
+            _ => panic!("expected ModuleFileNotFound"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_validation() {
+        let dir = temp_repo(
+            "validate",
+            r#"{"types":{"Default":{"parent":null}},"defaultType":"Default","configs":[{"modules":{"server":"server.json","nacos":"nacos.json"},"schema":null}],"plugins":{},
+               "jsonSchemas":{"server":"schemas/server.json","nacos":{"properties":{"timeout":{"type":"integer","default":3}}}}}"#,
+        );
+        fs::create_dir_all(dir.join("schemas")).unwrap();
+        fs::write(
+            dir.join("schemas/server.json"),
+            r#"{"type":"object","required":["name"],"properties":{"port":{"type":"integer","maximum":65535}}}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("Default/server.json"), r#"{"port":70000}"#).unwrap();
+        fs::write(dir.join("Default/nacos.json"), r#"{"port":0}"#).unwrap();
+
+        let mut manager = Manager::new(&dir, "Default").unwrap();
+        manager.get_configuration_mut().register_validator(
+            "nacos",
+            ModuleValidator::new()
+                .required("serverAddr")
+                .range("port", 1.0, 65535.0),
+        );
+        match manager.initialize() {
+            Err(ConfigurationError::ValidationFailed(v)) => {
+                let v: Vec<String> = v.iter().map(|v| v.to_string()).collect();
+                assert_eq!(
+                    v,
+                    vec![
+                        "nacos at serverAddr:is required",
+                        "nacos at port:0 is out of range [1,65535]",
+                        "server at name:is required",
+                        "server at port:70000 is greater than 65535",
+                    ]
+                );
+            }
+            _ => panic!("expected ValidationFailed"),
+        }
+
+        fs::write(dir.join("Default/server.json"), r#"{"name":"gateway"}"#).unwrap();
+        fs::write(
+            dir.join("Default/nacos.json"),
+            r#"{"serverAddr":"a","port":80}"#,
+        )
+        .unwrap();
+        manager.initialize().unwrap();
+        let nacos = manager
+            .get_configuration()
+            .get_config::<serde_json::Value>("nacos")
+            .unwrap();
+        assert_eq!(
+            nacos,
+            serde_json::json!({"serverAddr":"a","port":80,"timeout":3})
+        );
+
+        // a keyword the validator does not implement fails instead of passing silently
+        fs::write(
+            dir.join("schemas/server.json"),
+            r#"{"properties":{"name":{"type":"string","pattern":"^[a-z]+$"}}}"#,
+        )
+        .unwrap();
+        let mut manager = Manager::new(&dir, "Default").unwrap();
+        match manager.initialize() {
+            Err(ConfigurationError::ValidationFailed(v)) => {
+                let v: Vec<String> = v.iter().map(|v| v.to_string()).collect();
+                assert_eq!(
+                    v,
+                    vec![
+                        "server at .:schema keyword \"pattern\" at #/properties/name is not supported"
+                    ]
+                );
+            }
+            _ => panic!("expected ValidationFailed"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plugins() {
+        struct Reverse {}
+        impl Decryptor for Reverse {
+            fn decrypt(&self, sealed: &str) -> ConfigurationResult<String> {
+                Ok(sealed.chars().rev().collect())
+            }
+        }
+
+        let dir = temp_repo(
+            "plugin",
+            r#"{"types":{"Default":{"parent":null}},"defaultType":"Default","configs":[{"modules":{"nacos":"nacos.json"},"schema":null}],
+               "plugins":{"fileKv":{"path":"remote/kv.json"},"secret":{},"template":{"vars":{"region":"cn"}}}}"#,
+        );
+        fs::create_dir_all(dir.join("remote")).unwrap();
+        fs::write(
+            dir.join("remote/kv.json"),
+            r#"{"nacos":{"namespace":"remote"},"redis":{"password":"ENC[terces]","token":"ENC[}noiger{$]"}}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("Default/nacos.json"),
+            r#"{"serverAddr":"nacos.${region}","namespace":"public"}"#,
+        )
+        .unwrap();
+
+        let mut manager = Manager::new(&dir, "Default").unwrap();
+        let cfg = manager.get_configuration_mut();
+        cfg.register_plugin(FileKvPlugin::default());
+        cfg.register_plugin(SecretPlugin::new(Arc::new(Reverse {})));
+        cfg.register_plugin(TemplatePlugin::default());
+        manager.initialize().unwrap();
+        let cfg = manager.get_configuration();
+        assert_eq!(
+            cfg.get_config::<serde_json::Value>("nacos").unwrap(),
+            serde_json::json!({"serverAddr":"nacos.cn","namespace":"remote"})
+        );
+        // only provided by the plugin ,decrypted values are not rendered as templates
+        assert_eq!(
+            cfg.get_config::<serde_json::Value>("redis").unwrap(),
+            serde_json::json!({"password":"secret","token":"${region}"})
+        );
+
+        // root.json names a plugin that was never registered
+        let mut manager = Manager::new(&dir, "Default").unwrap();
+        manager
+            .get_configuration_mut()
+            .register_plugin(FileKvPlugin::default());
+        match manager.initialize() {
+            Err(ConfigurationError::UnknownPlugin { name, registered }) => {
+                assert_eq!(name, "template");
+                assert_eq!(registered, vec!["secret", "fileKv"]);
+            }
+            _ => panic!("expected UnknownPlugin"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_sealed() {
+        let key = SealKey::generate();
+        let dir = temp_repo(
+            "sealed",
+            r#"{"types":{"Default":{"parent":null}},"defaultType":"Default","configs":[{"modules":{"nacos":"nacos.json"},"schema":null}],
+               "plugins":{"secret":{"keyFile":"config.key"}}}"#,
+        );
+        fs::write(dir.join("config.key"), key.to_base64()).unwrap();
+        let sealed = key.seal("p@ss").unwrap();
+        fs::write(
+            dir.join("Default/nacos.json"),
+            format!(r#"{{"serverAddr":"a","auth":{{"password":"{}"}}}}"#, sealed),
+        )
+        .unwrap();
+
+        let manager = Manager::new_with_init(&dir, "Default").unwrap();
+        let cfg = manager.get_configuration();
+        assert_eq!(
+            cfg.get_config::<serde_json::Value>("nacos").unwrap(),
+            serde_json::json!({"serverAddr":"a","auth":{"password":"p@ss"}})
+        );
+        assert_eq!(
+            cfg.sealed_keys().unwrap().get("nacos").unwrap(),
+            &vec![String::from("auth.password")]
+        );
+
+        fs::write(dir.join("config.key"), SealKey::generate().to_base64()).unwrap();
+        match Manager::new_with_init(&dir, "Default")
+            .unwrap()
+            .get_configuration()
+            .get_config::<serde_json::Value>("nacos")
+        {
+            Err(ConfigurationError::PluginFailed { plugin, message }) => {
+                assert_eq!(plugin, "secret");
+                assert!(message.starts_with("decrypt nacos at auth.password failed"));
+            }
+            _ => panic!("expected PluginFailed"),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_repos() {
+        let platform = temp_repo(
+            "platform",
+            r#"{"types":{"Default":{"parent":null}},"defaultType":"Default","configs":[{"modules":{"nacos":"nacos.json","redis":"redis.json"},"schema":null}],"plugins":{}}"#,
+        );
+        fs::write(
+            platform.join("Default/nacos.json"),
+            r#"{"serverAddr":"platform"}"#,
+        )
+        .unwrap();
+        fs::write(platform.join("Default/redis.json"), r#"{"host":"redis"}"#).unwrap();
+        let service = temp_repo(
+            "service",
+            format!(
+                r#"{{"types":{{"Default":{{"parent":null}}}},"defaultType":"Default","configs":[{{"modules":{{"nacos":"nacos.json"}},"schema":null}}],"plugins":{{}},
+                   "repos":[{{"path":"../configuration-platform-{}","precedence":-1}}]}}"#,
+                std::process::id()
+            )
+            .as_str(),
+        );
+        fs::write(
+            service.join("Default/nacos.json"),
+            r#"{"serverAddr":"service"}"#,
+        )
+        .unwrap();
+
+        let manager = Manager::new_with_init(&service, "Default").unwrap();
+        let cfg = manager.get_configuration();
+        assert_eq!(cfg.get_repos().len(), 2);
+        assert_eq!(
+            cfg.get_config::<Nacos>("nacos").unwrap().server_addr,
+            "service"
+        );
+        assert_eq!(
+            cfg.get_config::<serde_json::Value>("redis").unwrap(),
+            serde_json::json!({"host":"redis"})
+        );
+        let sources = cfg.module_sources();
+        let nacos = sources.iter().find(|s| s.module == "nacos").unwrap();
+        assert_eq!(nacos.repo, service);
+        assert_eq!(
+            nacos.shadowed,
+            vec![service.join(format!("../configuration-platform-{}", std::process::id()))]
+        );
+
+        // 挂载同一个优先级 ,谁也不让谁
+        let mut manager = Manager::new(&platform, "Default").unwrap();
+        manager.get_configuration_mut().mount(service.join("."), 0);
+        match manager.initialize() {
+            Err(ConfigurationError::ModuleConflict { module, repos }) => {
+                assert_eq!(module, "nacos");
+                assert_eq!(repos, vec![platform.clone(), service.join(".")]);
+            }
+            _ => panic!("expected ModuleConflict"),
+        }
+
+        let mut manager = Manager::new(&platform, "Default").unwrap();
+        manager.get_configuration_mut().mount(&service, 1);
+        manager.initialize().unwrap();
+        assert_eq!(
+            manager
+                .get_configuration()
+                .get_config::<Nacos>("nacos")
+                .unwrap()
+                .server_addr,
+            "service"
+        );
+        fs::remove_dir_all(&platform).unwrap();
+        fs::remove_dir_all(&service).unwrap();
+    }
 }
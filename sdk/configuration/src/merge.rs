@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArrayPolicy {
+    // the array in `over` replaces the one in `base`
+    Replace,
+    // the elements in `over` are appended to the ones in `base`
+    Append,
+}
+
+impl Default for ArrayPolicy {
+    fn default() -> Self {
+        ArrayPolicy::Replace
+    }
+}
+
+// objects are merged key by key ,anything else in `over` replaces `base`
+pub fn merge(base: &mut Value, over: Value) {
+    merge_with(base, over, ArrayPolicy::Replace)
+}
+
+pub fn merge_with(base: &mut Value, over: Value, policy: ArrayPolicy) {
+    match (base, over) {
+        (Value::Object(b), Value::Object(o)) => {
+            for (k, v) in o {
+                match b.get_mut(&k) {
+                    Some(existing) => merge_with(existing, v, policy),
+                    None => {
+                        b.insert(k, v);
+                    }
+                }
+            }
+        }
+        (Value::Array(b), Value::Array(o)) if policy == ArrayPolicy::Append => b.extend(o),
+        (b, o) => *b = o,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::merge::{merge, merge_with, ArrayPolicy};
+    use serde_json::json;
+
+    #[test]
+    fn test_merge() {
+        let mut base = json!({"a":1,"b":{"c":2,"d":[1,2]},"e":"x"});
+        merge(&mut base, json!({"b":{"c":3,"d":[3]},"f":true}));
+        assert_eq!(base, json!({"a":1,"b":{"c":3,"d":[3]},"e":"x","f":true}));
+
+        let mut scalar = json!(1);
+        merge(&mut scalar, json!({"a":1}));
+        assert_eq!(scalar, json!({"a":1}));
+    }
+
+    #[test]
+    fn test_append() {
+        let mut base = json!({"nodes":[{"nodeId":"a"}],"tags":["x"]});
+        merge_with(
+            &mut base,
+            json!({"nodes":[{"nodeId":"b"}],"tags":"y"}),
+            ArrayPolicy::Append,
+        );
+        assert_eq!(
+            base,
+            json!({"nodes":[{"nodeId":"a"},{"nodeId":"b"}],"tags":"y"})
+        );
+    }
+}
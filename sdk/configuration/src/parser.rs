@@ -1,19 +1,19 @@
-use crate::enums::{ModuleKey, ModuleValue, Schema};
+use crate::enums::Schema;
 use crate::error::{ConfigurationError, ConfigurationResult};
 use crate::json::JsonValue;
+use crate::jsonnet;
 use crate::toml::TomlValue;
 use crate::value::ConfigValueTrait;
-use anyhow::Context;
+use crate::yaml::YamlValue;
 use serde::de::DeserializeOwned;
 use std::any::Any;
-use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
-use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 
 pub enum ParserEnums {
-    JSON(Rc<RefCell<DefaultParser>>),
+    JSON(Arc<Mutex<DefaultParser>>),
 }
 
 pub trait ConfigurationParser {
@@ -25,17 +25,22 @@ pub trait ConfigurationParser {
 }
 
 pub struct DefaultParser {
-    values: HashMap<ModuleKey, ModuleValue>,
     schema: Schema,
+    // only used by jsonnet
+    ext_vars: HashMap<String, String>,
 }
 
 impl DefaultParser {
     pub fn new(schema: Schema) -> Self {
         Self {
-            values: Default::default(),
             schema,
+            ext_vars: Default::default(),
         }
     }
+    pub fn with_ext_var(mut self, key: &str, value: &str) -> Self {
+        self.ext_vars.insert(String::from(key), String::from(value));
+        self
+    }
 }
 
 impl ConfigurationParser for DefaultParser {
@@ -44,12 +49,12 @@ impl ConfigurationParser for DefaultParser {
         module_name: String,
         file_path: PathBuf,
     ) -> ConfigurationResult<Box<dyn ConfigValueTrait<T>>> {
-        let data = fs::read_to_string(file_path.clone())
-            .with_context(|| format!("Failed to read config :{:?}", &file_path))?;
+        let data =
+            fs::read_to_string(file_path.clone()).map_err(|e| ConfigurationError::ReadError {
+                path: file_path.clone(),
+                source: e,
+            })?;
         let data = data.as_bytes().to_vec();
-        let value = ModuleValue::new(data.clone());
-        let key = ModuleKey::new(module_name);
-        self.values.insert(key, value.clone());
         match self.schema {
             Schema::JSON => {
                 let value = JsonValue::new(data);
@@ -59,6 +64,16 @@ impl ConfigurationParser for DefaultParser {
                 let value = TomlValue::new(data);
                 Ok(Box::new(value))
             }
+            Schema::YAML => {
+                let value = YamlValue::new(data);
+                Ok(Box::new(value))
+            }
+            Schema::JSONNET => {
+                let source = String::from_utf8_lossy(data.as_slice()).to_string();
+                let data = jsonnet::evaluate(&file_path, source.as_str(), &self.ext_vars)?;
+                let value = JsonValue::new(data);
+                Ok(Box::new(value))
+            }
         }
     }
 }
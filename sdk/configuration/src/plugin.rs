@@ -0,0 +1,332 @@
+// root.json 里的 plugins: 按名字注册插件 ,插件拿到自己的配置之后可以提供模块 ,或者改写模块的值
+use crate::error::{ConfigurationError, ConfigurationResult};
+use crate::json::strip_comments;
+use crate::seal::{SealKey, SealedDecryptor};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+pub type SharedPlugin = Arc<Mutex<Box<dyn ConfigurationPlugin>>>;
+
+pub struct PluginContext {
+    pub repo_root: PathBuf,
+    pub config_type: String,
+}
+
+// a registered plugin is enabled only when root.json has an entry for its name
+pub trait ConfigurationPlugin: Send {
+    fn name(&self) -> &str;
+
+    // enabled even without an entry in root.json ,`init` then gets a null
+    fn enabled_by_default(&self) -> bool {
+        false
+    }
+
+    // `settings` is `plugins.<name>` of root.json
+    fn init(&mut self, _settings: &Value, _ctx: &PluginContext) -> ConfigurationResult<()> {
+        Ok(())
+    }
+
+    // a source above the files and below the env vars ,None when the plugin does not know the module
+    fn load(&self, _module: &str) -> ConfigurationResult<Option<Value>> {
+        Ok(None)
+    }
+
+    // runs on the fully layered value ,in the order the plugins were registered ,the secret plugin last
+    fn transform(&self, _module: &str, _value: &mut Value) -> ConfigurationResult<()> {
+        Ok(())
+    }
+}
+
+fn settings<T: serde::de::DeserializeOwned>(
+    name: &str,
+    settings: &Value,
+) -> ConfigurationResult<T> {
+    T::deserialize(settings).map_err(|e| ConfigurationError::PluginFailed {
+        plugin: String::from(name),
+        message: format!("invalid settings:{}", e),
+    })
+}
+
+// walks every string in `value` ,`f` returns the replacement if any
+fn rewrite_strings<F>(value: &mut Value, path: &str, f: &F) -> ConfigurationResult<()>
+where
+    F: Fn(&str, &str) -> ConfigurationResult<Option<String>>,
+{
+    match value {
+        Value::String(s) => {
+            if let Some(v) = f(path, s.as_str())? {
+                *s = v;
+            }
+        }
+        Value::Array(a) => {
+            for (i, v) in a.iter_mut().enumerate() {
+                rewrite_strings(v, format!("{}[{}]", path, i).as_str(), f)?;
+            }
+        }
+        Value::Object(m) => {
+            for (k, v) in m.iter_mut() {
+                let p = if path == "." {
+                    k.clone()
+                } else {
+                    format!("{}.{}", path, k)
+                };
+                rewrite_strings(v, p.as_str(), f)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+////////////// file kv
+
+pub const FILE_KV: &'static str = "fileKv";
+
+#[derive(Deserialize)]
+struct FileKvSettings {
+    // relative to the repo ,top level keys are module names
+    path: String,
+}
+
+// stands in for a remote key-value store ,the file is read again on every load
+#[derive(Default)]
+pub struct FileKvPlugin {
+    path: Option<PathBuf>,
+}
+
+impl ConfigurationPlugin for FileKvPlugin {
+    fn name(&self) -> &str {
+        FILE_KV
+    }
+
+    fn init(&mut self, s: &Value, ctx: &PluginContext) -> ConfigurationResult<()> {
+        let s: FileKvSettings = settings(FILE_KV, s)?;
+        self.path = Some(ctx.repo_root.join(s.path));
+        Ok(())
+    }
+
+    fn load(&self, module: &str) -> ConfigurationResult<Option<Value>> {
+        let path = match &self.path {
+            Some(p) => p,
+            None => return Ok(None),
+        };
+        let data = fs::read(path).map_err(|e| ConfigurationError::ReadError {
+            path: path.clone(),
+            source: e,
+        })?;
+        let mut kv: Map<String, Value> =
+            serde_json::from_slice(strip_comments(data.as_slice()).as_slice())
+                .map_err(|e| ConfigurationError::from_json(path, ".", &e))?;
+        Ok(kv.remove(module))
+    }
+}
+
+////////////// secret
+
+pub const SECRET: &'static str = "secret";
+pub const SECRET_PREFIX: &'static str = "ENC[";
+pub const SECRET_SUFFIX: &'static str = "]";
+
+pub trait Decryptor: Send + Sync {
+    // `sealed` is the text between `ENC[` and `]`
+    fn decrypt(&self, sealed: &str) -> ConfigurationResult<String>;
+}
+
+#[derive(Deserialize, Default)]
+struct SecretSettings {
+    // relative to the repo ,used when neither KEY_ENV nor KEY_FILE_ENV is set
+    #[serde(rename = "keyFile", default)]
+    key_file: Option<String>,
+}
+
+// replaces every `ENC[...]` string with its plaintext ,built in and enabled by default
+#[derive(Default)]
+pub struct SecretPlugin {
+    // None decrypts with SealedDecryptor and the key from the environment
+    decryptor: Option<Arc<dyn Decryptor>>,
+}
+
+impl SecretPlugin {
+    pub fn new(decryptor: Arc<dyn Decryptor>) -> Self {
+        Self {
+            decryptor: Some(decryptor),
+        }
+    }
+}
+
+// leaves `ENC[...]` as it is ,for tools that only look at the sealed values
+pub struct KeepSealed {}
+
+impl Decryptor for KeepSealed {
+    fn decrypt(&self, sealed: &str) -> ConfigurationResult<String> {
+        Ok(format!("{}{}{}", SECRET_PREFIX, sealed, SECRET_SUFFIX))
+    }
+}
+
+pub fn unwrap_sealed(s: &str) -> Option<&str> {
+    s.strip_prefix(SECRET_PREFIX)
+        .and_then(|s| s.strip_suffix(SECRET_SUFFIX))
+}
+
+impl ConfigurationPlugin for SecretPlugin {
+    fn name(&self) -> &str {
+        SECRET
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+
+    fn init(&mut self, s: &Value, ctx: &PluginContext) -> ConfigurationResult<()> {
+        if self.decryptor.is_some() {
+            return Ok(());
+        }
+        let s: SecretSettings = if s.is_null() {
+            SecretSettings::default()
+        } else {
+            settings(SECRET, s)?
+        };
+        let key = match (SealKey::from_env()?, s.key_file) {
+            (Some(k), _) => Some(k),
+            (None, Some(f)) => Some(SealKey::from_file(&ctx.repo_root.join(f))?),
+            (None, None) => None,
+        };
+        self.decryptor = Some(Arc::new(SealedDecryptor::new(key)));
+        Ok(())
+    }
+
+    fn transform(&self, module: &str, value: &mut Value) -> ConfigurationResult<()> {
+        let decryptor = match &self.decryptor {
+            Some(d) => d,
+            None => return Ok(()),
+        };
+        rewrite_strings(value, ".", &|path, s| match unwrap_sealed(s) {
+            Some(sealed) => {
+                decryptor
+                    .decrypt(sealed)
+                    .map(Some)
+                    .map_err(|e| ConfigurationError::PluginFailed {
+                        plugin: String::from(SECRET),
+                        message: format!("decrypt {} at {} failed:{}", module, path, e),
+                    })
+            }
+            None => Ok(None),
+        })
+    }
+}
+
+////////////// template
+
+pub const TEMPLATE: &'static str = "template";
+
+#[derive(Deserialize, Default)]
+struct TemplateSettings {
+    #[serde(default)]
+    vars: HashMap<String, String>,
+}
+
+// `${name}` from the vars of the settings or the builtin `configType` ,`${env:NAME}` from the environment ,
+// `$${` for a literal `${`
+#[derive(Default)]
+pub struct TemplatePlugin {
+    vars: HashMap<String, String>,
+}
+
+impl TemplatePlugin {
+    fn render(&self, s: &str) -> Result<String, String> {
+        let mut ret = String::new();
+        let mut rest = s;
+        while let Some(start) = rest.find("${") {
+            // `$${` is a literal `${`
+            if rest[..start].ends_with('$') {
+                ret.push_str(&rest[..start - 1]);
+                ret.push_str("${");
+                rest = &rest[start + 2..];
+                continue;
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed ${{ in {:?}", s))?;
+            let name = &rest[start + 2..start + end];
+            let v = match name.strip_prefix("env:") {
+                Some(env) => std::env::var(env).ok(),
+                None => self.vars.get(name).cloned(),
+            };
+            ret.push_str(&rest[..start]);
+            ret.push_str(
+                v.ok_or_else(|| format!("unknown variable {}", name))?
+                    .as_str(),
+            );
+            rest = &rest[start + end + 1..];
+        }
+        ret.push_str(rest);
+        Ok(ret)
+    }
+}
+
+impl ConfigurationPlugin for TemplatePlugin {
+    fn name(&self) -> &str {
+        TEMPLATE
+    }
+
+    fn init(&mut self, s: &Value, ctx: &PluginContext) -> ConfigurationResult<()> {
+        let s: TemplateSettings = settings(TEMPLATE, s)?;
+        self.vars = s.vars;
+        self.vars
+            .entry(String::from("configType"))
+            .or_insert_with(|| ctx.config_type.clone());
+        Ok(())
+    }
+
+    fn transform(&self, module: &str, value: &mut Value) -> ConfigurationResult<()> {
+        rewrite_strings(value, ".", &|path, s| {
+            if !s.contains("${") {
+                return Ok(None);
+            }
+            self.render(s)
+                .map(Some)
+                .map_err(|e| ConfigurationError::PluginFailed {
+                    plugin: String::from(TEMPLATE),
+                    message: format!("render {} at {} failed:{}", module, path, e),
+                })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::plugin::{ConfigurationPlugin, PluginContext, TemplatePlugin};
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_template() {
+        let mut p = TemplatePlugin::default();
+        let ctx = PluginContext {
+            repo_root: PathBuf::from("."),
+            config_type: String::from("test1"),
+        };
+        p.init(&json!({"vars":{"region":"cn"}}), &ctx).unwrap();
+        std::env::set_var("CELL_TEMPLATE_TEST", "host");
+
+        let mut v =
+            json!({"addr":"${env:CELL_TEMPLATE_TEST}.${region}:80","tags":["${configType}"],"n":1});
+        p.transform("nacos", &mut v).unwrap();
+        assert_eq!(v, json!({"addr":"host.cn:80","tags":["test1"],"n":1}));
+
+        let mut v = json!({"shell":"$${HOME}/${region}","price":"$5"});
+        p.transform("nacos", &mut v).unwrap();
+        assert_eq!(v, json!({"shell":"${HOME}/cn","price":"$5"}));
+
+        let mut v = json!({"a":{"b":"${missing}"}});
+        let e = p.transform("nacos", &mut v).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "plugin template failed:render nacos at a.b failed:unknown variable missing"
+        );
+    }
+}
@@ -0,0 +1,199 @@
+// 加密的配置值: "ENC[AES256_GCM,<base64(nonce|密文)>]" ,key 来自环境变量或者 key 文件
+use crate::error::{ConfigurationError, ConfigurationResult};
+use crate::plugin::{unwrap_sealed, Decryptor, SECRET_PREFIX, SECRET_SUFFIX};
+use aes_gcm::aead::Aead;
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use rand::RngCore;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+// base64 of the 32 byte key
+pub const KEY_ENV: &'static str = "CELL_CONFIG_KEY";
+// a file holding the base64 key ,used when KEY_ENV is unset
+pub const KEY_FILE_ENV: &'static str = "CELL_CONFIG_KEY_FILE";
+pub const AES256_GCM: &'static str = "AES256_GCM";
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+#[derive(Clone)]
+pub struct SealKey {
+    key: [u8; KEY_LEN],
+}
+
+impl SealKey {
+    pub fn generate() -> Self {
+        let mut key = [0u8; KEY_LEN];
+        rand::thread_rng().fill_bytes(&mut key);
+        Self { key }
+    }
+
+    pub fn from_base64(s: &str) -> ConfigurationResult<Self> {
+        let data = base64::decode(s.trim())
+            .map_err(|e| ConfigurationError::StringError(format!("invalid key:{}", e)))?;
+        let key: [u8; KEY_LEN] = data.as_slice().try_into().map_err(|_| {
+            ConfigurationError::StringError(format!(
+                "invalid key ,expected {} bytes ,got {}",
+                KEY_LEN,
+                data.len()
+            ))
+        })?;
+        Ok(Self { key })
+    }
+
+    pub fn from_file(path: &Path) -> ConfigurationResult<Self> {
+        let data = fs::read_to_string(path).map_err(|e| ConfigurationError::ReadError {
+            path: path.to_path_buf(),
+            source: e,
+        })?;
+        Self::from_base64(data.as_str())
+    }
+
+    // KEY_ENV first ,then the file named by KEY_FILE_ENV
+    pub fn from_env() -> ConfigurationResult<Option<Self>> {
+        if let Ok(k) = std::env::var(KEY_ENV) {
+            return Self::from_base64(k.as_str()).map(Some);
+        }
+        match std::env::var(KEY_FILE_ENV) {
+            Ok(p) => Self::from_file(Path::new(p.as_str())).map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+
+    pub fn to_base64(&self) -> String {
+        base64::encode(self.key)
+    }
+
+    // the whole `ENC[...]` string ,a new nonce every time
+    pub fn seal(&self, plaintext: &str) -> ConfigurationResult<String> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let cipher = Aes256Gcm::new_from_slice(&self.key)
+            .map_err(|e| ConfigurationError::StringError(e.to_string()))?;
+        let encrypted = cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+            .map_err(|_| ConfigurationError::StringError(String::from("encrypt failed")))?;
+        let mut data = nonce.to_vec();
+        data.extend(encrypted);
+        Ok(format!(
+            "{}{},{}{}",
+            SECRET_PREFIX,
+            AES256_GCM,
+            base64::encode(data),
+            SECRET_SUFFIX
+        ))
+    }
+
+    fn open(&self, data: &str) -> ConfigurationResult<String> {
+        let data = base64::decode(data)
+            .map_err(|e| ConfigurationError::StringError(format!("invalid base64:{}", e)))?;
+        if data.len() <= NONCE_LEN {
+            return Err(ConfigurationError::StringError(String::from(
+                "sealed value is too short",
+            )));
+        }
+        let (nonce, encrypted) = data.split_at(NONCE_LEN);
+        let cipher = Aes256Gcm::new_from_slice(&self.key)
+            .map_err(|e| ConfigurationError::StringError(e.to_string()))?;
+        // 认证失败只有一种原因可报 ,key 不对或者内容被改过
+        let plain = cipher
+            .decrypt(Nonce::from_slice(nonce), encrypted)
+            .map_err(|_| {
+                ConfigurationError::StringError(String::from(
+                    "wrong key or the sealed value was modified",
+                ))
+            })?;
+        String::from_utf8(plain).map_err(|e| ConfigurationError::StringError(e.to_string()))
+    }
+}
+
+// the default decryptor of the secret plugin ,the key is only needed once a sealed value shows up
+pub struct SealedDecryptor {
+    key: Option<SealKey>,
+}
+
+impl SealedDecryptor {
+    pub fn new(key: Option<SealKey>) -> Self {
+        Self { key }
+    }
+}
+
+impl Decryptor for SealedDecryptor {
+    fn decrypt(&self, sealed: &str) -> ConfigurationResult<String> {
+        let (cipher, data) = sealed.split_once(',').ok_or_else(|| {
+            ConfigurationError::StringError(String::from("expected ENC[<cipher>,<data>]"))
+        })?;
+        if cipher != AES256_GCM {
+            return Err(ConfigurationError::StringError(format!(
+                "unsupported cipher {} ,expected {}",
+                cipher, AES256_GCM
+            )));
+        }
+        match &self.key {
+            Some(k) => k.open(data),
+            None => Err(ConfigurationError::StringError(format!(
+                "no key ,set {} or {}",
+                KEY_ENV, KEY_FILE_ENV
+            ))),
+        }
+    }
+}
+
+// paths of the sealed strings in `value` ,e.g. `auth.password` or `nodes[0].token`
+pub fn sealed_paths(value: &Value) -> Vec<String> {
+    let mut ret = Vec::new();
+    collect_sealed(value, "", &mut ret);
+    ret
+}
+
+fn collect_sealed(value: &Value, path: &str, out: &mut Vec<String>) {
+    match value {
+        Value::String(s) if unwrap_sealed(s).is_some() => out.push(String::from(path)),
+        Value::Array(a) => {
+            for (i, v) in a.iter().enumerate() {
+                collect_sealed(v, format!("{}[{}]", path, i).as_str(), out);
+            }
+        }
+        Value::Object(m) => {
+            for (k, v) in m {
+                let p = if path.is_empty() {
+                    k.clone()
+                } else {
+                    format!("{}.{}", path, k)
+                };
+                collect_sealed(v, p.as_str(), out);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::plugin::{unwrap_sealed, Decryptor};
+    use crate::seal::{sealed_paths, SealKey, SealedDecryptor};
+    use serde_json::json;
+
+    #[test]
+    fn test_seal() {
+        let key = SealKey::generate();
+        let sealed = key.seal("nacos-password").unwrap();
+        assert!(sealed.starts_with("ENC[AES256_GCM,"));
+        assert_ne!(sealed, key.seal("nacos-password").unwrap());
+
+        let key = SealKey::from_base64(key.to_base64().as_str()).unwrap();
+        let d = SealedDecryptor::new(Some(key));
+        let inner = unwrap_sealed(sealed.as_str()).unwrap();
+        assert_eq!(d.decrypt(inner).unwrap(), "nacos-password");
+
+        let other = SealedDecryptor::new(Some(SealKey::generate()));
+        assert!(other.decrypt(inner).is_err());
+        assert!(SealedDecryptor::new(None).decrypt(inner).is_err());
+        assert!(d.decrypt("age,abc").is_err());
+        assert!(SealKey::from_base64("c2hvcnQ=").is_err());
+
+        let v = json!({"a":"ENC[x]","b":{"c":["plain","ENC[y]"]},"d":1});
+        assert_eq!(sealed_paths(&v), vec!["a", "b.c[1]"]);
+    }
+}
@@ -1,6 +1,5 @@
-use crate::error::ConfigurationResult;
+use crate::error::{ConfigurationError, ConfigurationResult};
 use crate::value::ConfigValueTrait;
-use anyhow::Context;
 use serde::de::DeserializeOwned;
 
 pub struct TomlValue {
@@ -15,8 +14,24 @@ impl TomlValue {
 impl<T: DeserializeOwned + Clone> ConfigValueTrait<T> for TomlValue {
     fn as_object(&self) -> ConfigurationResult<T> {
         let str = String::from_utf8_lossy(self.data.as_slice()).to_string();
-        let config = toml::from_str(str.as_str())
-            .with_context(|| format!("deseralize config  to toml failed",))?;
+        let config = toml::from_str(str.as_str()).map_err(|e| {
+            let (line, column) = e
+                .span()
+                .map_or((0, 0), |span| line_col(str.as_str(), span.start));
+            ConfigurationError::TomlError {
+                line,
+                column,
+                message: String::from(e.message()),
+            }
+        })?;
         Ok(config)
     }
 }
+
+// 1-based ,same as serde_json
+fn line_col(s: &str, offset: usize) -> (usize, usize) {
+    let before = &s.as_bytes()[..offset.min(s.len())];
+    let line = before.iter().filter(|c| **c == b'\n').count() + 1;
+    let column = before.iter().rev().take_while(|c| **c != b'\n').count() + 1;
+    (line, column)
+}
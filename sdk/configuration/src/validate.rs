@@ -0,0 +1,512 @@
+// 模块校验: root.json 里声明的 json schema ,或者按模块名注册的校验规则 ,所有问题一次性报告
+use serde_json::{Map, Value};
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    pub module: String,
+    // same style as serde_path_to_error ,`.` is the module itself
+    pub json_path: String,
+    pub message: String,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}:{}", self.module, self.json_path, self.message)
+    }
+}
+
+type Check = Arc<dyn Fn(&Value, &mut Vec<(String, String)>) + Send + Sync>;
+
+// rules for one module ,keys are dotted paths like `auth.userName`
+#[derive(Clone, Default)]
+pub struct ModuleValidator {
+    checks: Vec<Check>,
+}
+
+impl ModuleValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn required(self, key: &str) -> Self {
+        let key = String::from(key);
+        self.check(move |v, out| {
+            if lookup(v, key.as_str()).map_or(true, |v| v.is_null()) {
+                out.push((key.clone(), String::from("is required")));
+            }
+        })
+    }
+
+    // missing keys are left to `required`
+    pub fn range(self, key: &str, min: f64, max: f64) -> Self {
+        let key = String::from(key);
+        self.check(move |v, out| match lookup(v, key.as_str()) {
+            None | Some(Value::Null) => {}
+            Some(Value::Number(n)) => {
+                let n = n.as_f64().unwrap_or_default();
+                if n < min || n > max {
+                    out.push((
+                        key.clone(),
+                        format!("{} is out of range [{},{}]", n, min, max),
+                    ));
+                }
+            }
+            Some(other) => out.push((key.clone(), format!("expected a number ,got {}", other))),
+        })
+    }
+
+    // any key of the object at `key` not in `allowed` is reported ,`.` is the module itself
+    pub fn deny_unknown(self, key: &str, allowed: &[&str]) -> Self {
+        let key = String::from(key);
+        let allowed: Vec<String> = allowed.iter().map(|s| String::from(*s)).collect();
+        self.check(move |v, out| {
+            if let Some(Value::Object(m)) = lookup(v, key.as_str()) {
+                for k in m.keys().filter(|k| !allowed.contains(k)) {
+                    out.push((join(key.as_str(), k), String::from("unknown field")));
+                }
+            }
+        })
+    }
+
+    // pushes (json_path ,message) for each problem found
+    pub fn check<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Value, &mut Vec<(String, String)>) + Send + Sync + 'static,
+    {
+        self.checks.push(Arc::new(f));
+        self
+    }
+
+    pub fn validate(&self, module: &str, value: &Value) -> Vec<Violation> {
+        let mut out = Vec::new();
+        for c in &self.checks {
+            c(value, &mut out);
+        }
+        to_violations(module, out)
+    }
+}
+
+fn lookup<'a>(v: &'a Value, key: &str) -> Option<&'a Value> {
+    if key == "." {
+        return Some(v);
+    }
+    key.split('.').try_fold(v, |v, k| match v {
+        Value::Array(a) => k.parse::<usize>().ok().and_then(|i| a.get(i)),
+        _ => v.get(k),
+    })
+}
+
+fn join(parent: &str, key: &str) -> String {
+    if parent == "." {
+        String::from(key)
+    } else {
+        format!("{}.{}", parent, key)
+    }
+}
+
+fn to_violations(module: &str, out: Vec<(String, String)>) -> Vec<Violation> {
+    out.into_iter()
+        .map(|(json_path, message)| Violation {
+            module: String::from(module),
+            json_path,
+            message,
+        })
+        .collect()
+}
+
+// the subset of json schema supported ,see SUPPORTED_KEYWORDS ,
+// schemas using anything else are rejected at initialize by `unsupported_keywords`
+pub fn validate_schema(module: &str, schema: &Value, value: &Value) -> Vec<Violation> {
+    let mut out = Vec::new();
+    check_schema(schema, value, ".", &mut out);
+    to_violations(module, out)
+}
+
+const SUPPORTED_KEYWORDS: &[&str] = &[
+    "type",
+    "enum",
+    "const",
+    "required",
+    "properties",
+    "additionalProperties",
+    "items",
+    "minItems",
+    "maxItems",
+    "minimum",
+    "maximum",
+    "exclusiveMinimum",
+    "exclusiveMaximum",
+    "minLength",
+    "maxLength",
+    "default",
+];
+
+// 只是说明 ,不影响校验
+const ANNOTATION_KEYWORDS: &[&str] = &[
+    "$schema",
+    "$id",
+    "$comment",
+    "title",
+    "description",
+    "examples",
+    "deprecated",
+    "readOnly",
+    "writeOnly",
+];
+
+// keywords validate_schema would silently skip ,pattern ,format ,$ref ,oneOf and the like
+pub fn unsupported_keywords(module: &str, schema: &Value) -> Vec<Violation> {
+    let mut out = Vec::new();
+    find_unsupported(schema, "", &mut out);
+    out.into_iter()
+        .map(|(location, keyword)| Violation {
+            module: String::from(module),
+            json_path: String::from("."),
+            message: format!(
+                "schema keyword {:?} at #{} is not supported",
+                keyword, location
+            ),
+        })
+        .collect()
+}
+
+fn find_unsupported(schema: &Value, location: &str, out: &mut Vec<(String, String)>) {
+    let schema = match schema {
+        Value::Object(s) => s,
+        _ => return,
+    };
+    for (k, v) in schema {
+        let here = format!("{}/{}", location, k);
+        let supported = match k.as_str() {
+            "properties" => {
+                if let Some(props) = v.as_object() {
+                    for (name, s) in props {
+                        find_unsupported(s, format!("{}/{}", here, name).as_str(), out);
+                    }
+                }
+                v.is_object()
+            }
+            "additionalProperties" => {
+                find_unsupported(v, here.as_str(), out);
+                v.is_object() || v.is_boolean()
+            }
+            // the tuple form of items is not supported
+            "items" => {
+                find_unsupported(v, here.as_str(), out);
+                v.is_object() || v.is_boolean()
+            }
+            // draft 4 used booleans here
+            "exclusiveMinimum" | "exclusiveMaximum" | "minimum" | "maximum" => v.is_number(),
+            k => SUPPORTED_KEYWORDS.contains(&k) || ANNOTATION_KEYWORDS.contains(&k),
+        };
+        if !supported {
+            out.push((location.to_string(), k.clone()));
+        }
+    }
+}
+
+fn check_schema(schema: &Value, value: &Value, path: &str, out: &mut Vec<(String, String)>) {
+    let schema = match schema {
+        Value::Object(s) => s,
+        // `true` accepts everything ,`false` nothing
+        Value::Bool(false) => {
+            out.push((String::from(path), String::from("is not allowed")));
+            return;
+        }
+        _ => return,
+    };
+    if let Some(t) = schema.get("type") {
+        let types: Vec<&str> = match t {
+            Value::String(s) => vec![s.as_str()],
+            Value::Array(a) => a.iter().filter_map(|v| v.as_str()).collect(),
+            _ => vec![],
+        };
+        if !types.is_empty() && !types.iter().any(|t| is_type(value, t)) {
+            out.push((
+                String::from(path),
+                format!("expected {} ,got {}", types.join(" or "), type_name(value)),
+            ));
+            // 类型都不对 ,其余的规则没有意义
+            return;
+        }
+    }
+    if let Some(Value::Array(e)) = schema.get("enum") {
+        if !e.contains(value) {
+            out.push((
+                String::from(path),
+                format!("{} is not one of {}", value, Value::Array(e.clone())),
+            ));
+        }
+    }
+    if let Some(c) = schema.get("const") {
+        if c != value {
+            out.push((String::from(path), format!("expected {}", c)));
+        }
+    }
+    match value {
+        Value::Object(m) => check_object(schema, m, path, out),
+        Value::Array(a) => {
+            if let Some(items) = schema.get("items") {
+                for (i, v) in a.iter().enumerate() {
+                    let p = if path == "." {
+                        format!("[{}]", i)
+                    } else {
+                        format!("{}[{}]", path, i)
+                    };
+                    check_schema(items, v, p.as_str(), out);
+                }
+            }
+            check_bounds(schema, "minItems", "maxItems", a.len(), "items", path, out);
+        }
+        Value::String(s) => {
+            check_bounds(
+                schema,
+                "minLength",
+                "maxLength",
+                s.chars().count(),
+                "characters",
+                path,
+                out,
+            );
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or_default();
+            let bound = |k: &str| schema.get(k).and_then(|v| v.as_f64());
+            if let Some(min) = bound("minimum").filter(|m| n < *m) {
+                out.push((String::from(path), format!("{} is less than {}", n, min)));
+            }
+            if let Some(max) = bound("maximum").filter(|m| n > *m) {
+                out.push((String::from(path), format!("{} is greater than {}", n, max)));
+            }
+            if let Some(min) = bound("exclusiveMinimum").filter(|m| n <= *m) {
+                out.push((
+                    String::from(path),
+                    format!("{} must be greater than {}", n, min),
+                ));
+            }
+            if let Some(max) = bound("exclusiveMaximum").filter(|m| n >= *m) {
+                out.push((
+                    String::from(path),
+                    format!("{} must be less than {}", n, max),
+                ));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn check_object(
+    schema: &Map<String, Value>,
+    m: &Map<String, Value>,
+    path: &str,
+    out: &mut Vec<(String, String)>,
+) {
+    if let Some(Value::Array(required)) = schema.get("required") {
+        for k in required.iter().filter_map(|k| k.as_str()) {
+            if !m.contains_key(k) {
+                out.push((join(path, k), String::from("is required")));
+            }
+        }
+    }
+    let properties = schema.get("properties").and_then(|p| p.as_object());
+    for (k, v) in m {
+        let p = join(path, k);
+        match properties.and_then(|props| props.get(k)) {
+            Some(s) => check_schema(s, v, p.as_str(), out),
+            None => match schema.get("additionalProperties") {
+                Some(Value::Bool(false)) => out.push((p, String::from("unknown field"))),
+                Some(s) => check_schema(s, v, p.as_str(), out),
+                None => {}
+            },
+        }
+    }
+}
+
+fn check_bounds(
+    schema: &Map<String, Value>,
+    min_key: &str,
+    max_key: &str,
+    len: usize,
+    unit: &str,
+    path: &str,
+    out: &mut Vec<(String, String)>,
+) {
+    let bound = |k: &str| schema.get(k).and_then(|v| v.as_u64());
+    if let Some(min) = bound(min_key).filter(|m| (len as u64) < *m) {
+        out.push((
+            String::from(path),
+            format!("expected at least {} {}", min, unit),
+        ));
+    }
+    if let Some(max) = bound(max_key).filter(|m| (len as u64) > *m) {
+        out.push((
+            String::from(path),
+            format!("expected at most {} {}", max, unit),
+        ));
+    }
+}
+
+fn is_type(v: &Value, t: &str) -> bool {
+    match t {
+        // 1.0 is an integer as well
+        "integer" => v.as_f64().map_or(false, |f| f.fract() == 0.0),
+        "number" => v.is_number(),
+        other => type_name(v) == other,
+    }
+}
+
+fn type_name(v: &Value) -> &'static str {
+    match v {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+// fills the `default` of the properties missing from `value` ,nested objects and array items included
+pub fn apply_schema_defaults(schema: &Value, value: &mut Value) {
+    if let Some(items) = schema.get("items") {
+        if let Value::Array(a) = value {
+            for v in a {
+                apply_schema_defaults(items, v);
+            }
+        }
+    }
+    let properties = match schema.get("properties").and_then(|p| p.as_object()) {
+        Some(p) => p,
+        None => return,
+    };
+    if value.is_null() && properties.values().any(|p| p.get("default").is_some()) {
+        *value = Value::Object(Map::new());
+    }
+    if let Value::Object(m) = value {
+        for (k, s) in properties {
+            match m.get_mut(k) {
+                Some(v) => apply_schema_defaults(s, v),
+                None => {
+                    if let Some(d) = s.get("default") {
+                        let mut d = d.clone();
+                        apply_schema_defaults(s, &mut d);
+                        m.insert(k.clone(), d);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::validate::{
+        apply_schema_defaults, unsupported_keywords, validate_schema, ModuleValidator,
+    };
+    use serde_json::json;
+
+    #[test]
+    fn test_validator() {
+        let v = ModuleValidator::new()
+            .required("serverAddr")
+            .range("port", 1.0, 65535.0)
+            .range("timeout", 0.0, 10.0)
+            .deny_unknown(".", &["serverAddr", "port", "timeout"]);
+        let ret = v.validate("nacos", &json!({"port":0,"severAddr":"x"}));
+        let ret: Vec<String> = ret.iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            ret,
+            vec![
+                "nacos at serverAddr:is required",
+                "nacos at port:0 is out of range [1,65535]",
+                "nacos at severAddr:unknown field",
+            ]
+        );
+        assert!(v
+            .validate("nacos", &json!({"serverAddr":"a","port":80}))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_schema() {
+        let schema = json!({
+            "type": "object",
+            "required": ["name"],
+            "additionalProperties": false,
+            "properties": {
+                "name": {"type": "string", "minLength": 1},
+                "port": {"type": "integer", "minimum": 1, "maximum": 65535, "default": 8080},
+                "mode": {"enum": ["a", "b"], "default": "a"},
+                "nodes": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {"weight": {"type": "number", "default": 1}}
+                    }
+                }
+            }
+        });
+        let value = json!({"port":"80","mode":"c","nodes":[{"weight":"x"}],"extra":1});
+        let ret: Vec<String> = validate_schema("server", &schema, &value)
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(
+            ret,
+            vec![
+                "server at name:is required",
+                "server at extra:unknown field",
+                "server at mode:\"c\" is not one of [\"a\",\"b\"]",
+                "server at nodes[0].weight:expected number ,got string",
+                "server at port:expected integer ,got string",
+            ]
+        );
+
+        let mut value = json!({"name":"gateway","nodes":[{}]});
+        apply_schema_defaults(&schema, &mut value);
+        assert_eq!(
+            value,
+            json!({"name":"gateway","port":8080,"mode":"a","nodes":[{"weight":1}]})
+        );
+        assert!(validate_schema("server", &schema, &value).is_empty());
+    }
+
+    #[test]
+    fn test_unsupported_keywords() {
+        let schema = json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "title": "server",
+            "type": "object",
+            "properties": {
+                "name": {"type": "string", "pattern": "^[a-z]+$"},
+                "port": {"type": "integer", "exclusiveMinimum": true},
+                "nodes": {"type": "array", "items": [{"type": "object"}]}
+            },
+            "oneOf": [{"required": ["name"]}]
+        });
+        let ret: Vec<String> = unsupported_keywords("server", &schema)
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        assert_eq!(
+            ret,
+            vec![
+                "server at .:schema keyword \"oneOf\" at # is not supported",
+                "server at .:schema keyword \"pattern\" at #/properties/name is not supported",
+                "server at .:schema keyword \"items\" at #/properties/nodes is not supported",
+                "server at .:schema keyword \"exclusiveMinimum\" at #/properties/port is not supported",
+            ]
+        );
+
+        let schema = json!({"properties": {"port": {"type": "integer"}}});
+        assert!(unsupported_keywords("server", &schema).is_empty());
+        // 1.0 passes as an integer ,1.5 does not
+        assert!(validate_schema("server", &schema, &json!({"port": 1.0})).is_empty());
+        assert_eq!(
+            validate_schema("server", &schema, &json!({"port": 1.5})).len(),
+            1
+        );
+    }
+}
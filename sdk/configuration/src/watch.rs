@@ -0,0 +1,425 @@
+// 配置热更新: 监听模块文件 ,重新加载并校验之后才替换 ,然后通知订阅者
+use crate::cfg::Configuration;
+use crate::error::{ConfigurationError, ConfigurationResult};
+use crate::CONFIGURATION;
+use logsdk::common::LogLevel;
+use notify::{Event, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// 调用时不持有 subscribers 的锁 ,listener 里可以再 subscribe / on_change
+type Listener = Arc<Mutex<dyn Fn(&str, Arc<Value>) + Send>>;
+// (id ,sender) ,the id finds the sender again once its receiver is gone
+type Channel = (u64, Sender<Arc<Value>>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchMode {
+    // inotify / fsevents ,falls back to polling when unavailable
+    Auto,
+    Poll(Duration),
+}
+
+#[derive(Clone, Debug)]
+pub struct WatchOptions {
+    pub mode: WatchMode,
+    // events within this window are handled as one reload
+    pub debounce: Duration,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        WatchOptions {
+            mode: WatchMode::Auto,
+            debounce: Duration::from_millis(200),
+        }
+    }
+}
+
+impl WatchOptions {
+    pub fn with_mode(mut self, mode: WatchMode) -> Self {
+        self.mode = mode;
+        self
+    }
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+}
+
+enum Msg {
+    Fs(notify::Result<Event>),
+    Stop,
+}
+
+#[derive(Default)]
+struct Subscribers {
+    next_id: u64,
+    channels: HashMap<String, Vec<Channel>>,
+    listeners: Vec<Listener>,
+}
+
+impl Subscribers {
+    fn add_channel(&mut self, module: &str, tx: Sender<Arc<Value>>) {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.channels
+            .entry(String::from(module))
+            .or_default()
+            .push((id, tx));
+    }
+
+    fn remove_channels(&mut self, module: &str, ids: &[u64]) {
+        if let Some(senders) = self.channels.get_mut(module) {
+            senders.retain(|(id, _)| !ids.contains(id));
+        }
+    }
+}
+
+fn publish(subscribers: &Arc<Mutex<Subscribers>>, module: &str, value: Arc<Value>) {
+    let (senders, listeners) = {
+        let s = subscribers.lock().unwrap();
+        (
+            s.channels.get(module).cloned().unwrap_or_default(),
+            s.listeners.clone(),
+        )
+    };
+    // 订阅方已经 drop 的直接移除
+    let closed: Vec<u64> = senders
+        .iter()
+        .filter(|(_, tx)| tx.send(value.clone()).is_err())
+        .map(|(id, _)| *id)
+        .collect();
+    if !closed.is_empty() {
+        subscribers
+            .lock()
+            .unwrap()
+            .remove_channels(module, closed.as_slice());
+    }
+    for l in listeners {
+        (l.lock().unwrap())(module, value.clone());
+    }
+}
+
+pub struct Subscription<T> {
+    rx: Receiver<Arc<Value>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> Subscription<T> {
+    // blocks until the module changes ,None once the watcher is stopped
+    pub fn recv(&self) -> Option<ConfigurationResult<T>> {
+        self.rx.recv().ok().map(|v| Self::convert(&v))
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<ConfigurationResult<T>> {
+        self.rx
+            .recv_timeout(timeout)
+            .ok()
+            .map(|v| Self::convert(&v))
+    }
+
+    pub fn try_recv(&self) -> Option<ConfigurationResult<T>> {
+        self.rx.try_recv().ok().map(|v| Self::convert(&v))
+    }
+
+    fn convert(v: &Value) -> ConfigurationResult<T> {
+        Ok(T::deserialize(v)?)
+    }
+}
+
+pub struct ConfigWatcher {
+    subscribers: Arc<Mutex<Subscribers>>,
+    stop: Sender<Msg>,
+    worker: Option<JoinHandle<()>>,
+    // dropping it stops the notifications
+    _watcher: Box<dyn Watcher + Send>,
+}
+
+impl ConfigWatcher {
+    // `cfg` must be initialized ,its cache is shared with every clone of it
+    pub fn start(cfg: Configuration, options: WatchOptions) -> ConfigurationResult<Self> {
+        let mut roots: Vec<PathBuf> = cfg.get_repos().iter().map(|r| r.path.clone()).collect();
+        if roots.is_empty() {
+            roots.push(cfg.get_repo_root().cloned().unwrap_or_default());
+        }
+        let roots: Vec<PathBuf> = roots
+            .into_iter()
+            .map(|r| fs::canonicalize(&r).unwrap_or(r))
+            .collect();
+        let (tx, rx) = channel::<Msg>();
+        let watcher = create_watcher(&roots, options.mode, tx.clone())?;
+        let subscribers: Arc<Mutex<Subscribers>> = Default::default();
+        let s = subscribers.clone();
+        let debounce = options.debounce;
+        let worker = thread::Builder::new()
+            .name(String::from("configuration-watcher"))
+            .spawn(move || run(cfg, rx, s, debounce))?;
+        cinfo!(CONFIGURATION, "watching configuration under {:?}", roots);
+        Ok(ConfigWatcher {
+            subscribers,
+            stop: tx,
+            worker: Some(worker),
+            _watcher: watcher,
+        })
+    }
+
+    pub fn subscribe<T: DeserializeOwned>(&self, module: &str) -> Subscription<T> {
+        let (tx, rx) = channel();
+        self.subscribers.lock().unwrap().add_channel(module, tx);
+        Subscription {
+            rx,
+            _marker: PhantomData,
+        }
+    }
+
+    // called on the watcher thread for every module that changed
+    pub fn on_change<F>(&self, f: F)
+    where
+        F: Fn(&str, Arc<Value>) + Send + 'static,
+    {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .listeners
+            .push(Arc::new(Mutex::new(f)));
+    }
+
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        let _ = self.stop.send(Msg::Stop);
+        if let Some(h) = self.worker.take() {
+            let _ = h.join();
+        }
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn watch_all<W: Watcher>(mut w: W, roots: &[PathBuf]) -> notify::Result<W> {
+    for root in roots {
+        w.watch(root, RecursiveMode::Recursive)?;
+    }
+    Ok(w)
+}
+
+fn create_watcher(
+    roots: &[PathBuf],
+    mode: WatchMode,
+    tx: Sender<Msg>,
+) -> ConfigurationResult<Box<dyn Watcher + Send>> {
+    if mode == WatchMode::Auto {
+        let native = RecommendedWatcher::new(forward(tx.clone()), notify::Config::default())
+            .and_then(|w| watch_all(w, roots));
+        match native {
+            Ok(w) => return Ok(Box::new(w)),
+            Err(e) => cwarn!(
+                CONFIGURATION,
+                "native file watcher unavailable ,fall back to polling:{}",
+                e
+            ),
+        }
+    }
+    let interval = match mode {
+        WatchMode::Poll(d) => d,
+        WatchMode::Auto => Duration::from_secs(2),
+    };
+    // mtime 只精确到秒 ,同一秒内的两次修改要靠内容比较
+    let config = notify::Config::default()
+        .with_poll_interval(interval)
+        .with_compare_contents(true);
+    let w = watch_all(PollWatcher::new(forward(tx), config)?, roots)?;
+    Ok(Box::new(w))
+}
+
+fn forward(tx: Sender<Msg>) -> impl FnMut(notify::Result<Event>) + Send + 'static {
+    move |res| {
+        let _ = tx.send(Msg::Fs(res));
+    }
+}
+
+fn run(
+    cfg: Configuration,
+    rx: Receiver<Msg>,
+    subscribers: Arc<Mutex<Subscribers>>,
+    debounce: Duration,
+) {
+    let files = cfg.watched_files();
+    // 先把当前值放进缓存 ,之后只有内容真的变了才通知
+    for m in files.values().flatten().collect::<BTreeSet<&String>>() {
+        if cfg.cached(m).is_none() {
+            if let Ok(v) = cfg.load_module(m) {
+                cfg.store(m, v);
+            }
+        }
+    }
+    loop {
+        let mut changed: BTreeSet<PathBuf> = BTreeSet::new();
+        match rx.recv() {
+            Ok(Msg::Fs(res)) => collect(res, &mut changed),
+            Ok(Msg::Stop) | Err(_) => return,
+        }
+        loop {
+            match rx.recv_timeout(debounce) {
+                Ok(Msg::Fs(res)) => collect(res, &mut changed),
+                Ok(Msg::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+                Err(RecvTimeoutError::Timeout) => break,
+            }
+        }
+        let modules: BTreeSet<&String> = changed
+            .iter()
+            .filter_map(|p| files.get(p))
+            .flatten()
+            .collect();
+        for m in modules {
+            reload(&cfg, m.as_str(), &subscribers);
+        }
+    }
+}
+
+fn collect(res: notify::Result<Event>, changed: &mut BTreeSet<PathBuf>) {
+    match res {
+        Ok(e) => changed.extend(e.paths),
+        Err(e) => cerror!(CONFIGURATION, "watch configuration failed:{}", e),
+    }
+}
+
+fn reload(cfg: &Configuration, module: &str, subscribers: &Arc<Mutex<Subscribers>>) {
+    // 解析失败时保留旧值 ,编辑到一半的文件不会生效
+    let value = match cfg.load_module(module) {
+        Ok(v) => v,
+        Err(e) => {
+            cerror!(
+                CONFIGURATION,
+                "reload module {} failed ,keep the previous value:{}",
+                module,
+                e
+            );
+            return;
+        }
+    };
+    let violations = cfg.validate_module(module, &value);
+    if !violations.is_empty() {
+        cerror!(
+            CONFIGURATION,
+            "reload module {} rejected ,keep the previous value:{}",
+            module,
+            ConfigurationError::ValidationFailed(violations)
+        );
+        return;
+    }
+    if cfg.cached(module).as_ref() == Some(&value) {
+        return;
+    }
+    cinfo!(CONFIGURATION, "module {} reloaded", module);
+    cfg.store(module, value.clone());
+    publish(subscribers, module, Arc::new(value));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::manager::Manager;
+    use crate::watch::{publish, Subscribers, WatchMode, WatchOptions};
+    use serde_json::{json, Value};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::mpsc::channel;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    fn temp_repo(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("configuration-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("Default")).unwrap();
+        fs::write(
+            dir.join("root.json"),
+            r#"{"types":{"Default":{"parent":null}},"defaultType":"Default","configs":[{"modules":{"nacos":"nacos.json"},"schema":null}],"plugins":{}}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("Default/nacos.json"), r#"{"serverAddr":"a"}"#).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_reload() {
+        let dir = temp_repo("watch");
+        let manager = Manager::new_with_init(&dir, "Default").unwrap();
+        let options = WatchOptions::default()
+            .with_mode(WatchMode::Poll(Duration::from_millis(20)))
+            .with_debounce(Duration::from_millis(20));
+        let watcher = manager.watch(options).unwrap();
+        let sub = watcher.subscribe::<Value>("nacos");
+        let (tx, rx) = channel();
+        watcher.on_change(move |m, v| {
+            let _ = tx.send((String::from(m), v));
+        });
+        // 让 poll watcher 先记下文件的初始状态
+        thread::sleep(Duration::from_millis(100));
+
+        // an invalid edit is ignored
+        fs::write(dir.join("Default/nacos.json"), r#"{"serverAddr":"#).unwrap();
+        assert!(sub.recv_timeout(Duration::from_millis(300)).is_none());
+
+        fs::write(dir.join("Default/nacos.json"), r#"{"serverAddr":"b"}"#).unwrap();
+        let v = sub.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        assert_eq!(v, json!({"serverAddr":"b"}));
+        let (m, _) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(m, "nacos");
+        // the cache is shared ,the manager sees the new value as well
+        let v = manager
+            .get_configuration()
+            .get_config::<Value>("nacos")
+            .unwrap();
+        assert_eq!(v, json!({"serverAddr":"b"}));
+
+        watcher.stop();
+        assert!(sub.recv().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_publish_reentrant() {
+        let subscribers = Arc::new(Mutex::new(Subscribers::default()));
+        let (tx, rx) = channel();
+        let (dropped, _) = channel();
+        {
+            let mut s = subscribers.lock().unwrap();
+            s.add_channel("nacos", tx);
+            s.add_channel("nacos", dropped);
+        }
+        // 在 listener 里再订阅 ,不能死锁
+        let inner = subscribers.clone();
+        subscribers
+            .lock()
+            .unwrap()
+            .listeners
+            .push(Arc::new(Mutex::new(move |m: &str, _v: Arc<Value>| {
+                let (tx, _) = channel();
+                inner.lock().unwrap().add_channel(m, tx);
+            })));
+
+        publish(&subscribers, "nacos", Arc::new(json!({"serverAddr":"b"})));
+        assert_eq!(*rx.try_recv().unwrap(), json!({"serverAddr":"b"}));
+        let s = subscribers.lock().unwrap();
+        let ids: Vec<u64> = s.channels["nacos"].iter().map(|(id, _)| *id).collect();
+        // the dropped receiver is gone ,the one added by the listener is kept
+        assert_eq!(ids, vec![1, 3]);
+    }
+}
@@ -0,0 +1,20 @@
+use crate::error::ConfigurationResult;
+use crate::value::ConfigValueTrait;
+use serde::de::DeserializeOwned;
+
+pub struct YamlValue {
+    data: Vec<u8>,
+}
+
+impl YamlValue {
+    pub fn new(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+}
+
+impl<T: DeserializeOwned + Clone> ConfigValueTrait<T> for YamlValue {
+    fn as_object(&self) -> ConfigurationResult<T> {
+        let ret = serde_yaml::from_slice::<T>(self.data.as_slice())?;
+        Ok(ret)
+    }
+}
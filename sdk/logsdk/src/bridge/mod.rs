@@ -0,0 +1,263 @@
+// 接入 log / tracing: 依赖库(hyper ,rocket ,tokio)的日志也走 logsdk 的 level 与格式
+use crate::common::{LogFields, LogLevel};
+use crate::log4rs::DEFAULT_LOGGER;
+use crate::module::{CellModule, Module};
+use crate::{enabled, writer};
+use arc_swap::ArcSwap;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::RwLock;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::Layer;
+
+// target of the events logsdk forwards into tracing ,never bridged back
+pub const FORWARD_TARGET: &'static str = "logsdk";
+
+lazy_static! {
+    // (target prefix ,module name)
+    static ref TARGET_MAPPINGS: ArcSwap<Vec<(String, String)>> = ArcSwap::from_pointee(Vec::new());
+    // 按 module 名缓存 ,数量受限于 module 而不是 target
+    static ref BRIDGED_MODULES: RwLock<HashMap<String, &'static CellModule>> =
+        RwLock::new(HashMap::new());
+}
+
+// e.g. map_target("rocket", "HTTP_ROCKET") ,the longest matching prefix wins
+pub fn map_target(prefix: &str, module: &str) {
+    TARGET_MAPPINGS.rcu(|old| {
+        let mut v = Vec::clone(old);
+        v.retain(|(p, _)| p != prefix);
+        v.push((String::from(prefix), String::from(module)));
+        v
+    });
+}
+
+fn module_name_of(target: &str) -> String {
+    let mappings = TARGET_MAPPINGS.load();
+    let mapped = mappings
+        .iter()
+        .filter(|(prefix, _)| target.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len());
+    if let Some((_, module)) = mapped {
+        return module.clone();
+    }
+    // hyper::proto::h1 => HYPER
+    target
+        .split("::")
+        .next()
+        .unwrap_or(target)
+        .replace('-', "_")
+        .to_ascii_uppercase()
+}
+
+pub fn target_module(target: &str) -> &'static CellModule {
+    let name = module_name_of(target);
+    if let Some(m) = BRIDGED_MODULES.read().unwrap().get(&name) {
+        return m;
+    }
+    let mut modules = BRIDGED_MODULES.write().unwrap();
+    if let Some(m) = modules.get(&name) {
+        return m;
+    }
+    let leaked: &'static str = Box::leak(name.clone().into_boxed_str());
+    let m: &'static CellModule = Box::leak(Box::new(CellModule::new(0, leaked, &LogLevel::Trace)));
+    modules.insert(name, m);
+    m
+}
+
+fn from_log_level(l: log::Level) -> LogLevel {
+    match l {
+        log::Level::Trace => LogLevel::Trace,
+        log::Level::Debug => LogLevel::Debug,
+        log::Level::Info => LogLevel::Info,
+        log::Level::Warn => LogLevel::Warn,
+        log::Level::Error => LogLevel::Error,
+    }
+}
+
+fn from_tracing_level(l: &tracing::Level) -> LogLevel {
+    match *l {
+        tracing::Level::TRACE => LogLevel::Trace,
+        tracing::Level::DEBUG => LogLevel::Debug,
+        tracing::Level::INFO => LogLevel::Info,
+        tracing::Level::WARN => LogLevel::Warn,
+        tracing::Level::ERROR => LogLevel::Error,
+    }
+}
+
+////////////// log
+pub struct LogBridge {}
+
+impl log::Log for LogBridge {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        enabled(
+            target_module(metadata.target()),
+            from_log_level(metadata.level()),
+        )
+    }
+
+    fn log(&self, record: &log::Record) {
+        let m = target_module(record.target());
+        DEFAULT_LOGGER.log(
+            m,
+            from_log_level(record.level()),
+            record.file().unwrap_or(record.target()),
+            record.line().unwrap_or(0),
+            record.args().to_string().as_str(),
+        )
+    }
+
+    fn flush(&self) {
+        writer::flush();
+    }
+}
+
+pub fn install_log_bridge() -> Result<(), log::SetLoggerError> {
+    log::set_boxed_logger(Box::new(LogBridge {}))?;
+    log::set_max_level(log::LevelFilter::Trace);
+    Ok(())
+}
+
+////////////// tracing
+#[derive(Default)]
+struct FieldVisitor {
+    message: String,
+    fields: LogFields,
+}
+
+impl Visit for FieldVisitor {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.fields
+            .push((field.name(), serde_json::Value::from(value)));
+    }
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.fields
+            .push((field.name(), serde_json::Value::from(value)));
+    }
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.fields
+            .push((field.name(), serde_json::Value::from(value)));
+    }
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = String::from(value);
+            return;
+        }
+        self.fields
+            .push((field.name(), serde_json::Value::from(value)));
+    }
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+            return;
+        }
+        self.fields.push((
+            field.name(),
+            serde_json::Value::from(format!("{:?}", value)),
+        ));
+    }
+}
+
+pub struct CellLayer {}
+
+impl<S: Subscriber> Layer<S> for CellLayer {
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        metadata.target() != FORWARD_TARGET
+            && enabled(
+                target_module(metadata.target()),
+                from_tracing_level(metadata.level()),
+            )
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if metadata.target() == FORWARD_TARGET {
+            return;
+        }
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        DEFAULT_LOGGER.log_with_fields(
+            target_module(metadata.target()),
+            from_tracing_level(metadata.level()),
+            metadata.file().unwrap_or(metadata.target()),
+            metadata.line().unwrap_or(0),
+            visitor.message.as_str(),
+            visitor.fields,
+        )
+    }
+}
+
+pub fn install_tracing_bridge() -> Result<(), tracing::subscriber::SetGlobalDefaultError> {
+    tracing::subscriber::set_global_default(tracing_subscriber::registry().with(CellLayer {}))
+}
+
+// 已经有其它 logger / subscriber 时保持原样
+pub fn install() {
+    let _ = install_log_bridge();
+    let _ = install_tracing_bridge();
+}
+
+// logsdk 自己的日志转发给 tracing ,从而挂到当前 span 下
+pub fn forward_to_tracing(m: &dyn Module, l: LogLevel, msg: &str) {
+    let module = m.name();
+    match l {
+        LogLevel::Trace => tracing::trace!(target: FORWARD_TARGET, module, "{}", msg),
+        LogLevel::Debug => tracing::debug!(target: FORWARD_TARGET, module, "{}", msg),
+        LogLevel::Info => tracing::info!(target: FORWARD_TARGET, module, "{}", msg),
+        LogLevel::Warn => tracing::warn!(target: FORWARD_TARGET, module, "{}", msg),
+        LogLevel::Error => tracing::error!(target: FORWARD_TARGET, module, "{}", msg),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bridge::{map_target, target_module, CellLayer, FORWARD_TARGET};
+    use crate::capture::capture;
+    use crate::common::LogLevel;
+    use crate::module::Module;
+    use crate::test_config_lock;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_target_module() {
+        assert_eq!(target_module("hyper::proto::h1::io").name(), "HYPER");
+        assert_eq!(target_module("tokio-util::codec").name(), "TOKIO_UTIL");
+        map_target("rocket", "HTTP_ROCKET");
+        map_target("rocket::server", "HTTP_SERVER");
+        assert_eq!(target_module("rocket::launch").name(), "HTTP_ROCKET");
+        assert_eq!(target_module("rocket::server::conn").name(), "HTTP_SERVER");
+        // interned ,the same module is handed out every time
+        assert!(std::ptr::eq(
+            target_module("hyper"),
+            target_module("hyper::client")
+        ));
+    }
+
+    #[test]
+    fn test_tracing_layer() {
+        let _lock = test_config_lock();
+        let c = capture();
+        let subscriber = tracing_subscriber::registry().with(CellLayer {});
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request", id = 7);
+            let _guard = span.enter();
+            tracing::warn!(target: "mybridge::conn", user = "nori", count = 3, "hello from tracing");
+            tracing::info!(target: FORWARD_TARGET, "ignored");
+        });
+
+        let entries = c.find("MYBRIDGE", LogLevel::Warn, "hello from tracing");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].message, "hello from tracing");
+        assert_eq!(
+            entries[0].fields,
+            vec![
+                ("user", serde_json::Value::from("nori")),
+                ("count", serde_json::Value::from(3)),
+            ]
+        );
+        // 转发回 tracing 的日志不会再被桥接
+        assert_eq!(c.entries().len(), 1);
+    }
+}
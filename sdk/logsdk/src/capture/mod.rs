@@ -0,0 +1,197 @@
+// 测试用: 把日志记录到内存里,方便对输出做断言
+use crate::common::{LogEntry, LogLevel};
+use crate::log::MLogger;
+use std::cell::RefCell;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+// 所有线程上活跃的 capture 数 ,为 0 时日志路径上不查 thread local
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static SCOPES: RefCell<Vec<CaptureBuffer>> = RefCell::new(Vec::new());
+}
+
+#[derive(Clone, Default)]
+pub struct CaptureBuffer {
+    entries: Arc<Mutex<Vec<LogEntry>>>,
+}
+
+impl CaptureBuffer {
+    pub fn push(&self, entry: LogEntry) {
+        self.entries.lock().unwrap().push(entry)
+    }
+
+    pub fn entries(&self) -> Vec<LogEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear()
+    }
+
+    pub fn find(&self, module: &str, l: LogLevel, needle: &str) -> Vec<LogEntry> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|e| e.module.name() == module && e.log_level == l && e.message.contains(needle))
+            .cloned()
+            .collect()
+    }
+
+    pub fn contains(&self, module: &str, l: LogLevel, needle: &str) -> bool {
+        !self.find(module, l, needle).is_empty()
+    }
+
+    #[track_caller]
+    pub fn assert_logged(&self, module: &str, l: LogLevel, needle: &str) {
+        if !self.contains(module, l, needle) {
+            panic!(
+                "expected {:?} log from {} containing {:?} ,captured:\n{}",
+                l,
+                module,
+                needle,
+                self.dump()
+            );
+        }
+    }
+
+    #[track_caller]
+    pub fn assert_not_logged(&self, module: &str, l: LogLevel, needle: &str) {
+        if self.contains(module, l, needle) {
+            panic!(
+                "unexpected {:?} log from {} containing {:?} ,captured:\n{}",
+                l,
+                module,
+                needle,
+                self.dump()
+            );
+        }
+    }
+
+    fn dump(&self) -> String {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|e| format!("  {:?} {} {}", e.log_level, e.module.name(), e.message))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+// 作为 Logger 的输出使用 ,所有经过这个 Logger 的日志都会被记录
+pub struct CaptureLogger {
+    buffer: CaptureBuffer,
+}
+
+impl CaptureLogger {
+    pub fn new(buffer: CaptureBuffer) -> Self {
+        CaptureLogger { buffer }
+    }
+}
+
+impl MLogger for CaptureLogger {
+    fn log(&self, entry: LogEntry) {
+        self.buffer.push(entry)
+    }
+}
+
+// records what the current thread logs until it is dropped ,scopes may nest
+pub struct CaptureGuard {
+    buffer: CaptureBuffer,
+}
+
+impl Deref for CaptureGuard {
+    type Target = CaptureBuffer;
+
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+impl Drop for CaptureGuard {
+    fn drop(&mut self) {
+        SCOPES.with(|s| {
+            s.borrow_mut().pop();
+        });
+        ACTIVE.fetch_sub(1, Ordering::Release);
+    }
+}
+
+pub fn capture() -> CaptureGuard {
+    let buffer = CaptureBuffer::default();
+    SCOPES.with(|s| s.borrow_mut().push(buffer.clone()));
+    ACTIVE.fetch_add(1, Ordering::Release);
+    CaptureGuard { buffer }
+}
+
+pub(crate) fn capturing() -> bool {
+    ACTIVE.load(Ordering::Acquire) > 0
+}
+
+pub(crate) fn record(entry: &LogEntry) {
+    SCOPES.with(|s| {
+        for buffer in s.borrow().iter() {
+            buffer.push(entry.clone());
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::capture::{capture, capturing, CaptureBuffer, CaptureLogger};
+    use crate::common::LogLevel;
+    use crate::log::Logger;
+    use crate::module::CellModule;
+    use crate::test_config_lock;
+    use std::thread;
+
+    static M: &CellModule = &CellModule::new(1, "CAPTURE", &LogLevel::Info);
+
+    #[test]
+    fn test_capture_scope() {
+        let _lock = test_config_lock();
+        let outer = capture();
+        assert!(capturing());
+        cerror!(M, "outer {}", 1);
+        {
+            let inner = capture();
+            cerror!(M, "inner {}", 2, { user: "nori" });
+            inner.assert_logged("CAPTURE", LogLevel::Error, "inner 2");
+            inner.assert_not_logged("CAPTURE", LogLevel::Error, "outer");
+        }
+        cerror!(M, "after");
+        // 其它线程的日志不会进来
+        thread::spawn(|| {
+            cerror!(M, "other thread");
+        })
+        .join()
+        .unwrap();
+
+        outer.assert_logged("CAPTURE", LogLevel::Error, "outer 1");
+        outer.assert_logged("CAPTURE", LogLevel::Error, "inner 2");
+        outer.assert_logged("CAPTURE", LogLevel::Error, "after");
+        outer.assert_not_logged("CAPTURE", LogLevel::Error, "other thread");
+        assert_eq!(outer.entries().len(), 3);
+    }
+
+    #[test]
+    fn test_capture_logger() {
+        let _lock = test_config_lock();
+        let buffer = CaptureBuffer::default();
+        let logger = Logger::new(Box::new(CaptureLogger::new(buffer.clone())));
+        logger.warn(M, String::from("disk almost full"));
+        buffer.assert_logged("CAPTURE", LogLevel::Warn, "almost full");
+        assert!(!buffer.contains("CAPTURE", LogLevel::Error, "almost full"));
+    }
+
+    #[test]
+    #[should_panic(expected = "expected Error log from CAPTURE")]
+    fn test_assert_logged_fails() {
+        let c = capture();
+        c.assert_logged("CAPTURE", LogLevel::Error, "never");
+    }
+}
@@ -1,9 +1,10 @@
 use crate::module::Module;
 use std::fmt::{write, Display, Formatter};
+use std::str::FromStr;
 
 static log_level_simple: [&'static str; 5] = ["TRACE", "DEBUG", "INFO", "WARN", "ERROR"];
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 pub enum LogLevel {
     Trace = 0,
     Debug = 1,
@@ -30,16 +31,86 @@ impl Clone for LogLevel {
 
 impl Copy for LogLevel {}
 
+#[derive(Debug, PartialEq)]
+pub struct ParseLogLevelError(pub String);
+
+impl Display for ParseLogLevelError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown log level:{}", self.0)
+    }
+}
+
+impl std::error::Error for ParseLogLevelError {}
+
+impl FromStr for LogLevel {
+    type Err = ParseLogLevelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "TRACE" => Ok(LogLevel::Trace),
+            "DEBUG" => Ok(LogLevel::Debug),
+            "INFO" => Ok(LogLevel::Info),
+            "WARN" => Ok(LogLevel::Warn),
+            "ERROR" => Ok(LogLevel::Error),
+            _ => Err(ParseLogLevelError(String::from(s))),
+        }
+    }
+}
+
 pub fn get_simple_loglevel(l: LogLevel) -> &'static str {
     // let v = *l;
     log_level_simple[l.get_value()]
 }
 
-#[derive(Debug)]
+// key-value pairs passed through `c*!(m, "msg", { key: value })`
+pub type LogFields = Vec<(&'static str, serde_json::Value)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    // colored single line ,for humans
+    Console,
+    // one json object per line ,for log aggregators
+    Json,
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        LogFormat::Console
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseLogFormatError(pub String);
+
+impl Display for ParseLogFormatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown log format:{} ,expected console or json", self.0)
+    }
+}
+
+impl std::error::Error for ParseLogFormatError {}
+
+impl FromStr for LogFormat {
+    type Err = ParseLogFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "console" => Ok(LogFormat::Console),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(ParseLogFormatError(String::from(s))),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct LogEntry {
+    // the formatted line
     pub msg: String,
     pub log_level: LogLevel,
     pub module: &'static dyn Module,
+    // what the caller passed ,before formatting
+    pub message: String,
+    pub fields: LogFields,
 }
 
 impl Display for LogEntry {
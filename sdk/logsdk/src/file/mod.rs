@@ -0,0 +1,340 @@
+// 文件输出: 按大小/按天滚动,保留 N 个历史文件,可选 gzip ,并可按 module 路由到不同文件
+use crate::common::LogEntry;
+use arc_swap::ArcSwap;
+use chrono::{Local, NaiveDate};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use lazy_static::lazy_static;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+lazy_static! {
+    static ref FILE_APPENDERS: ArcSwap<Vec<Arc<FileAppender>>> = ArcSwap::from_pointee(Vec::new());
+}
+
+#[derive(Clone, Debug)]
+pub struct FileAppenderProperty {
+    pub path: PathBuf,
+    // exact module names or `PREFIX_*` ,empty means every module
+    pub modules: Vec<String>,
+    // roll once the file would grow past this many bytes
+    pub max_size: Option<u64>,
+    // roll on the first write of a new day
+    pub daily: bool,
+    // rolled files kept as path.1 .. path.N ,path.1 being the newest
+    pub retention: usize,
+    pub compress: bool,
+}
+
+impl FileAppenderProperty {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        FileAppenderProperty {
+            path: path.as_ref().to_path_buf(),
+            modules: Vec::new(),
+            max_size: None,
+            daily: false,
+            retention: 7,
+            compress: false,
+        }
+    }
+    pub fn with_modules(mut self, modules: Vec<String>) -> Self {
+        self.modules = modules;
+        self
+    }
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+    pub fn with_daily(mut self, daily: bool) -> Self {
+        self.daily = daily;
+        self
+    }
+    pub fn with_retention(mut self, retention: usize) -> Self {
+        self.retention = retention;
+        self
+    }
+    pub fn with_compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    pub fn accept(&self, module_name: &str) -> bool {
+        if self.modules.is_empty() {
+            return true;
+        }
+        self.modules.iter().any(|p| match p.strip_suffix('*') {
+            Some(prefix) => module_name.starts_with(prefix),
+            None => p == module_name,
+        })
+    }
+}
+
+pub struct RollingFileWriter {
+    property: FileAppenderProperty,
+    file: Option<File>,
+    size: u64,
+    day: NaiveDate,
+}
+
+impl RollingFileWriter {
+    pub fn new(property: FileAppenderProperty) -> io::Result<Self> {
+        if let Some(dir) = property.path.parent() {
+            if !dir.as_os_str().is_empty() {
+                fs::create_dir_all(dir)?;
+            }
+        }
+        let mut ret = RollingFileWriter {
+            property,
+            file: None,
+            size: 0,
+            day: today(),
+        };
+        ret.open()?;
+        Ok(ret)
+    }
+
+    fn open(&mut self) -> io::Result<()> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.property.path)?;
+        let meta = file.metadata()?;
+        self.size = meta.len();
+        // 沿用已有文件的日期,重启之后跨天也能滚动
+        self.day = meta
+            .modified()
+            .map(|t| chrono::DateTime::<Local>::from(t).naive_local().date())
+            .unwrap_or_else(|_| today());
+        self.file = Some(file);
+        Ok(())
+    }
+
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let incoming = line.len() as u64 + 1;
+        if self.should_roll(incoming, today()) {
+            self.roll()?;
+        }
+        if self.file.is_none() {
+            self.open()?;
+        }
+        let file = self.file.as_mut().unwrap();
+        file.write_all(line.as_bytes())?;
+        file.write_all(b"\n")?;
+        self.size += incoming;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.file.as_mut() {
+            Some(f) => f.flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn should_roll(&self, incoming: u64, today: NaiveDate) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+        if self.property.daily && today != self.day {
+            return true;
+        }
+        self.property
+            .max_size
+            .map_or(false, |max| self.size + incoming > max)
+    }
+
+    fn archive_path(&self, index: usize) -> PathBuf {
+        let mut p = self.property.path.clone().into_os_string();
+        p.push(format!(".{}", index));
+        if self.property.compress {
+            p.push(".gz");
+        }
+        PathBuf::from(p)
+    }
+
+    fn roll(&mut self) -> io::Result<()> {
+        self.file = None;
+        let retention = self.property.retention;
+        if retention == 0 {
+            fs::remove_file(&self.property.path)?;
+        } else {
+            let oldest = self.archive_path(retention);
+            if oldest.exists() {
+                fs::remove_file(&oldest)?;
+            }
+            for i in (1..retention).rev() {
+                let from = self.archive_path(i);
+                if from.exists() {
+                    fs::rename(&from, self.archive_path(i + 1))?;
+                }
+            }
+            if self.property.compress {
+                gzip_to(&self.property.path, &self.archive_path(1))?;
+                fs::remove_file(&self.property.path)?;
+            } else {
+                fs::rename(&self.property.path, self.archive_path(1))?;
+            }
+        }
+        self.open()
+    }
+}
+
+fn today() -> NaiveDate {
+    Local::now().naive_local().date()
+}
+
+fn gzip_to(from: &Path, to: &Path) -> io::Result<()> {
+    let mut input = File::open(from)?;
+    let mut encoder = GzEncoder::new(File::create(to)?, Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+// 文件里不需要 console 的颜色
+fn strip_ansi(s: &str) -> String {
+    let mut ret = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            for n in chars.by_ref() {
+                if n.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            continue;
+        }
+        ret.push(c);
+    }
+    ret
+}
+
+pub struct FileAppender {
+    property: FileAppenderProperty,
+    writer: Mutex<RollingFileWriter>,
+}
+
+impl FileAppender {
+    pub fn new(property: FileAppenderProperty) -> io::Result<Self> {
+        let writer = RollingFileWriter::new(property.clone())?;
+        Ok(FileAppender {
+            property,
+            writer: Mutex::new(writer),
+        })
+    }
+
+    pub fn append(&self, entry: &LogEntry) {
+        if !self.property.accept(entry.module.name()) {
+            return;
+        }
+        let line = strip_ansi(entry.msg.as_str());
+        let mut writer = self.writer.lock().unwrap();
+        // 不能再走 logger ,否则会递归
+        if let Err(e) = writer.write_line(line.as_str()) {
+            eprintln!("write log file {:?} failed:{}", self.property.path, e);
+        }
+    }
+
+    pub fn flush(&self) {
+        let _ = self.writer.lock().unwrap().flush();
+    }
+}
+
+// 全部打开成功才替换,传空 vec 即关闭文件输出
+pub fn setup_file_appenders(properties: Vec<FileAppenderProperty>) -> io::Result<()> {
+    let mut appenders = Vec::new();
+    for p in properties {
+        appenders.push(Arc::new(FileAppender::new(p)?));
+    }
+    FILE_APPENDERS.store(Arc::new(appenders));
+    Ok(())
+}
+
+pub fn flush_files() {
+    for appender in FILE_APPENDERS.load().iter() {
+        appender.flush();
+    }
+}
+
+pub fn append_to_files(entry: &LogEntry) {
+    for appender in FILE_APPENDERS.load().iter() {
+        appender.append(entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::file::{strip_ansi, FileAppenderProperty, RollingFileWriter};
+    use chrono::Duration;
+    use flate2::read::GzDecoder;
+    use std::fs;
+    use std::io::Read;
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("logsdk-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_size_rolling() {
+        let dir = temp_dir("size");
+        let path = dir.join("app.log");
+        let property = FileAppenderProperty::new(&path)
+            .with_max_size(20)
+            .with_retention(2)
+            .with_compress(true);
+        let mut w = RollingFileWriter::new(property).unwrap();
+        for i in 0..4 {
+            w.write_line(format!("line-{:0>10}", i).as_str()).unwrap();
+        }
+        w.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "line-0000000003\n");
+        assert!(dir.join("app.log.1.gz").exists());
+        assert!(dir.join("app.log.2.gz").exists());
+        assert!(!dir.join("app.log.3.gz").exists());
+
+        let mut s = String::new();
+        GzDecoder::new(fs::File::open(dir.join("app.log.1.gz")).unwrap())
+            .read_to_string(&mut s)
+            .unwrap();
+        assert_eq!(s, "line-0000000002\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_daily_rolling() {
+        let dir = temp_dir("daily");
+        let path = dir.join("app.log");
+        let property = FileAppenderProperty::new(&path).with_daily(true);
+        let mut w = RollingFileWriter::new(property).unwrap();
+        w.write_line("yesterday").unwrap();
+        w.day = w.day - Duration::days(1);
+        w.write_line("today").unwrap();
+        w.flush().unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "today\n");
+        assert_eq!(
+            fs::read_to_string(dir.join("app.log.1")).unwrap(),
+            "yesterday\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_accept() {
+        let p = FileAppenderProperty::new("x.log")
+            .with_modules(vec![String::from("HTTP_*"), String::from("EXTENSION")]);
+        assert!(p.accept("HTTP_DISPATCH"));
+        assert!(p.accept("EXTENSION"));
+        assert!(!p.accept("EXTENSION_MANAGER"));
+        assert!(FileAppenderProperty::new("x.log").accept("ANY"));
+        assert_eq!(strip_ansi("\x1b[32mINFO\x1b[0m msg"), "INFO msg");
+    }
+}
@@ -1,18 +1,22 @@
+pub mod bridge;
 pub mod common;
+pub mod file;
 pub mod module;
 #[macro_use]
 pub mod log4rs;
 #[macro_use]
 pub mod clog;
+pub mod capture;
+pub mod limit;
+pub mod writer;
 
-use crate::common::{get_simple_loglevel, LogLevel};
-use crate::log::{
-    const_new_default, const_set_loglevel, CellLoggerConfiguration, ColorProperty, Logger, FF, FFF,
-};
+use crate::common::{get_simple_loglevel, LogFields, LogFormat, LogLevel};
+use crate::log::{CellLoggerConfiguration, ColorProperty, Logger, FF, FFF};
 use crate::module::{CellModule, Module};
 use ansi_term::Color::Red;
 use ansi_term::Colour::*;
 use ansi_term::{ANSIGenericString, Color};
+use arc_swap::ArcSwap;
 use backtrace::Backtrace;
 use cell_base_common::cellerrors::{CellError, ErrorEnum};
 use chrono::Local;
@@ -22,6 +26,9 @@ use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Display, Formatter};
 use std::iter::Map;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+pub use serde_json;
 
 const DATE_FORMAT_STR: &'static str = "%Y/%m/%d %H:%M:%S";
 const SKIP_CALLER: usize = 3;
@@ -37,19 +44,14 @@ pub struct A {
 
 lazy_static! {
     static ref a: A = A { m: HashMap::new() };
+    // 读多写少,arc-swap 保证运行时替换配置是线程安全的
+    static ref CONFIGURATION: ArcSwap<CellLoggerConfiguration> =
+        ArcSwap::from_pointee(CellLoggerConfiguration::default());
 }
 static mut MAP: Option<HashMap<Box<String>, Box<String>>> = None;
-const DEFAULT_BLACK_LIST: &'static [&'static str] = &[
-    "src/backtrace/libunwind.rs",
-    "src/backtrace/mod.rs",
-    "src/capture.rs",
-    "rust-cell/sdk/logsdk/src/lib.rs",
-];
 
 static ERROR_SETUP_FAILED: &ErrorEnum = &ErrorEnum::Error(1, "setup config failed");
-static mut CONFIGURATION: &CellLoggerConfiguration = &const_new_default();
-static mut GLOBAL_LOGLEVEL: &LogLevel = &LogLevel::Trace;
-static DEFAULT_MODULE: CellModule = CellModule::new(1, "ALL", unsafe { GLOBAL_LOGLEVEL });
+static DEFAULT_MODULE: CellModule = CellModule::new(1, "ALL", &LogLevel::Trace);
 
 const DEFAULT_TRACE_LEVEL_COLOR: PaintF = |v| Green.paint(v);
 
@@ -65,19 +67,20 @@ const DEFAULT_FATAL_LEVEL_COLOR: PaintF = |v| Red.paint(v);
 const DEFAULT_MODULE_COLOR: PaintF = |v| Cyan.paint(v);
 
 pub mod log {
-    use crate::common::{get_simple_loglevel, LogEntry, LogLevel};
+    use crate::bridge::forward_to_tracing;
+    use crate::capture::{capturing, record};
+    use crate::common::{get_simple_loglevel, LogEntry, LogFields, LogFormat, LogLevel};
     use crate::module::Module;
     use crate::{
-        default_format_msg, get_current_time_str, get_log_info, stack_trace, PaintF, CONFIGURATION,
-        DATE_FORMAT_STR, DEFAULT_BLACK_LIST, DEFAULT_DEBUG_LEVEL_COLOR, DEFAULT_ERROR_LEVEL_COLOR,
-        DEFAULT_INFO_LEVEL_COLOR, DEFAULT_MODULE_COLOR, DEFAULT_TRACE_LEVEL_COLOR,
-        DEFAULT_WARN_LEVEL_COLOR,
+        enabled, format_entry, forward_enabled, get_current_time_str, PaintF, DATE_FORMAT_STR,
+        DEFAULT_DEBUG_LEVEL_COLOR, DEFAULT_ERROR_LEVEL_COLOR, DEFAULT_INFO_LEVEL_COLOR,
+        DEFAULT_MODULE_COLOR, DEFAULT_TRACE_LEVEL_COLOR, DEFAULT_WARN_LEVEL_COLOR,
     };
-    use backtrace::Backtrace;
     use std::borrow::Cow;
     use std::collections::{HashMap, HashSet};
     use std::fmt;
     use std::fmt::{format, Debug};
+    use std::panic::Location;
 
     pub trait MLogger {
         fn log(&self, entry: LogEntry);
@@ -91,21 +94,26 @@ pub mod log {
     unsafe impl Sync for Logger {}
 
     impl Logger {
-        // TODO macros
+        // caller 的位置在编译期确定,不再运行时抓取 backtrace
+        #[track_caller]
         pub fn info(&self, m: &'static dyn Module, msg: String) {
-            let bt = &Backtrace::new();
-            let (file_str, line_no) = stack_trace(bt);
-            self.log(m, LogLevel::Info, file_str, line_no, msg.as_str())
+            let loc = Location::caller();
+            self.log(m, LogLevel::Info, loc.file(), loc.line(), msg.as_str())
         }
+        #[track_caller]
         pub fn error(&self, m: &'static dyn Module, msg: String) {
-            let bt = &Backtrace::new();
-            let (file_str, line_no) = stack_trace(bt);
-            self.log(m, LogLevel::Error, file_str, line_no, msg.as_str())
+            let loc = Location::caller();
+            self.log(m, LogLevel::Error, loc.file(), loc.line(), msg.as_str())
         }
+        #[track_caller]
         pub fn warn(&self, m: &'static dyn Module, msg: String) {
-            let bt = &Backtrace::new();
-            let (file_str, line_no) = stack_trace(bt);
-            self.log(m, LogLevel::Warn, file_str, line_no, msg.as_str())
+            let loc = Location::caller();
+            self.log(m, LogLevel::Warn, loc.file(), loc.line(), msg.as_str())
+        }
+        #[track_caller]
+        pub fn debug(&self, m: &'static dyn Module, msg: String) {
+            let loc = Location::caller();
+            self.log(m, LogLevel::Debug, loc.file(), loc.line(), msg.as_str())
         }
         pub fn log(
             &self,
@@ -115,7 +123,29 @@ pub mod log {
             line_no: u32,
             format_msg: &str,
         ) {
-            let entry = LoggerEntryContext::create_log_entry(m, l, file_str, line_no, format_msg);
+            self.log_with_fields(m, l, file_str, line_no, format_msg, Vec::new())
+        }
+        pub fn log_with_fields(
+            &self,
+            m: &'static dyn Module,
+            l: LogLevel,
+            file_str: &str,
+            line_no: u32,
+            format_msg: &str,
+            fields: LogFields,
+        ) {
+            if !enabled(m, l) {
+                return;
+            }
+            if forward_enabled() {
+                forward_to_tracing(m, l, format_msg);
+            }
+            let entry =
+                LoggerEntryContext::build_log_entry(m, l, file_str, line_no, format_msg, fields);
+            // 没有 capture 时不做 clone
+            if capturing() {
+                record(&entry);
+            }
             self.logger.log(entry)
         }
         pub fn new(logger: Box<dyn MLogger>) -> Self {
@@ -132,35 +162,124 @@ pub mod log {
             file_str: &str,
             line_no: u32,
             format_msg: &str,
+        ) -> LogEntry {
+            Self::create_log_entry_with_fields(m, l, file_str, line_no, format_msg, &Vec::new())
+        }
+        pub fn create_log_entry_with_fields(
+            m: &'static dyn Module,
+            l: LogLevel,
+            file_str: &str,
+            line_no: u32,
+            format_msg: &str,
+            fields: &LogFields,
+        ) -> LogEntry {
+            Self::build_log_entry(m, l, file_str, line_no, format_msg, fields.clone())
+        }
+        fn build_log_entry(
+            m: &'static dyn Module,
+            l: LogLevel,
+            file_str: &str,
+            line_no: u32,
+            format_msg: &str,
+            fields: LogFields,
         ) -> LogEntry {
             let ret = LogEntry {
-                msg: default_format_msg(m, file_str, line_no, l, format_msg),
+                msg: format_entry(m, file_str, line_no, l, format_msg, &fields),
                 log_level: l,
                 module: m,
+                message: String::from(format_msg),
+                fields,
             };
             return ret;
         }
     }
 
+    #[derive(Clone)]
     pub struct CellLoggerConfiguration {
-        pub global_loglevel: &'static LogLevel,
-        pub black_list: &'static [&'static str],
-        pub color_property: &'static ColorProperty,
+        pub global_loglevel: LogLevel,
+        // keyed by CellModule name
+        pub module_loglevels: HashMap<String, LogLevel>,
+        pub color_property: ColorProperty,
+        pub format: LogFormat,
+        // capture a backtrace for Error level entries ,it is expensive
+        pub error_backtrace: bool,
+        // also emit every entry as a tracing event ,see bridge::forward_to_tracing
+        pub forward_to_tracing: bool,
     }
 
-    impl Clone for CellLoggerConfiguration {
-        fn clone(&self) -> Self {
+    impl Default for CellLoggerConfiguration {
+        fn default() -> Self {
             CellLoggerConfiguration {
-                global_loglevel: &LogLevel::Trace,
-                black_list: self.black_list,
-                color_property: self.color_property,
+                global_loglevel: LogLevel::Info,
+                module_loglevels: HashMap::new(),
+                color_property: ColorProperty::default_color_property(),
+                format: LogFormat::default(),
+                error_backtrace: false,
+                forward_to_tracing: false,
             }
         }
     }
 
-    impl Copy for CellLoggerConfiguration {}
+    impl CellLoggerConfiguration {
+        pub fn with_global_loglevel(mut self, l: LogLevel) -> Self {
+            self.global_loglevel = l;
+            self
+        }
+        pub fn with_module_loglevel(mut self, module: &str, l: LogLevel) -> Self {
+            self.module_loglevels.insert(String::from(module), l);
+            self
+        }
+        pub fn with_format(mut self, f: LogFormat) -> Self {
+            self.format = f;
+            self
+        }
+        pub fn with_error_backtrace(mut self, enable: bool) -> Self {
+            self.error_backtrace = enable;
+            self
+        }
+        pub fn with_forward_to_tracing(mut self, enable: bool) -> Self {
+            self.forward_to_tracing = enable;
+            self
+        }
+        pub fn with_color_property(mut self, c: ColorProperty) -> Self {
+            self.color_property = c;
+            self
+        }
 
-    impl CellLoggerConfiguration {}
+        // 优先级: 精确匹配 > 最长的前缀匹配(HTTP_*) > max(global, module 自带的 level)
+        pub fn get_loglevel(&self, module: &dyn Module) -> LogLevel {
+            let name = module.name();
+            if let Some(l) = self.module_loglevels.get(name) {
+                return *l;
+            }
+            let mut matched: Option<(usize, LogLevel)> = None;
+            for (pattern, l) in self.module_loglevels.iter() {
+                let prefix = match pattern.strip_suffix('*') {
+                    Some(p) => p,
+                    None => continue,
+                };
+                if !name.starts_with(prefix) {
+                    continue;
+                }
+                if matched.map_or(true, |(len, _)| prefix.len() > len) {
+                    matched = Some((prefix.len(), *l));
+                }
+            }
+            if let Some((_, l)) = matched {
+                return l;
+            }
+            let own = *module.log_level();
+            if own.gt(self.global_loglevel) {
+                own
+            } else {
+                self.global_loglevel
+            }
+        }
+
+        pub fn enabled(&self, module: &dyn Module, l: LogLevel) -> bool {
+            !self.get_loglevel(module).gt(l)
+        }
+    }
 
     pub trait FF {
         fn handle(&self, c: &mut CellLoggerConfiguration);
@@ -176,49 +295,9 @@ pub mod log {
         }
     }
 
-    // TODO,macros
-    pub const fn const_new_default() -> CellLoggerConfiguration {
-        let mut ret = CellLoggerConfiguration {
-            global_loglevel: &LogLevel::Info,
-            black_list: DEFAULT_BLACK_LIST,
-            color_property: &ColorProperty {
-                trace_level_color: DEFAULT_TRACE_LEVEL_COLOR,
-                debug_level_color: DEFAULT_DEBUG_LEVEL_COLOR,
-                info_level_color: DEFAULT_INFO_LEVEL_COLOR,
-                warn_level_color: DEFAULT_WARN_LEVEL_COLOR,
-                error_level_color: DEFAULT_ERROR_LEVEL_COLOR,
-                default_module_color: DEFAULT_MODULE_COLOR,
-            },
-        };
-        const_copy(&ret)
-    }
-
-    pub const fn const_set_loglevel(l: &'static LogLevel) -> CellLoggerConfiguration {
-        let mut ret = CellLoggerConfiguration {
-            global_loglevel: l,
-            black_list: DEFAULT_BLACK_LIST,
-            color_property: &ColorProperty {
-                trace_level_color: DEFAULT_TRACE_LEVEL_COLOR,
-                debug_level_color: DEFAULT_DEBUG_LEVEL_COLOR,
-                info_level_color: DEFAULT_INFO_LEVEL_COLOR,
-                warn_level_color: DEFAULT_WARN_LEVEL_COLOR,
-                error_level_color: DEFAULT_ERROR_LEVEL_COLOR,
-                default_module_color: DEFAULT_MODULE_COLOR,
-            },
-        };
-        const_copy(&ret)
-    }
-
-    pub const fn const_copy(from: &CellLoggerConfiguration) -> CellLoggerConfiguration {
-        CellLoggerConfiguration {
-            global_loglevel: from.global_loglevel,
-            black_list: from.black_list,
-            color_property: from.color_property,
-        }
-    }
-
     pub trait ColorTrait: ToOwned + Sized + Debug {}
 
+    #[derive(Clone)]
     pub struct ColorProperty {
         pub trace_level_color: PaintF,
         pub debug_level_color: PaintF,
@@ -244,64 +323,169 @@ pub mod log {
 
 type PaintF = fn(&str) -> ANSIGenericString<'_, str>;
 
-pub fn setup_logger_configuration(cfg: &'static CellLoggerConfiguration) {
-    setup_logger_configuration_inner(|| cfg);
+pub fn logger_configuration() -> Arc<CellLoggerConfiguration> {
+    CONFIGURATION.load_full()
+}
+
+pub fn setup_logger_configuration(cfg: CellLoggerConfiguration) {
+    CONFIGURATION.store(Arc::new(cfg));
+}
+
+// copy-on-write ,concurrent updates are retried so none of them get lost
+pub fn update_logger_configuration<F>(f: F)
+where
+    F: Fn(&mut CellLoggerConfiguration),
+{
+    CONFIGURATION.rcu(|old| {
+        let mut cfg = CellLoggerConfiguration::clone(old);
+        f(&mut cfg);
+        cfg
+    });
+}
+
+// cheap check ,call it before building the message
+pub fn enabled(m: &dyn Module, l: LogLevel) -> bool {
+    CONFIGURATION.load().enabled(m, l)
+}
+
+pub fn set_global_level(l: LogLevel) {
+    update_logger_configuration(|c| c.global_loglevel = l);
+}
+
+pub fn set_module_level(module: &str, l: LogLevel) {
+    update_logger_configuration(|c| {
+        c.module_loglevels.insert(String::from(module), l);
+    });
+}
+
+pub fn remove_module_level(module: &str) {
+    update_logger_configuration(|c| {
+        c.module_loglevels.remove(module);
+    });
+}
+
+// hot reload: replaces the levels as a whole ,colors and black list are kept
+pub fn reload(global: LogLevel, modules: HashMap<String, LogLevel>) {
+    update_logger_configuration(|c| {
+        c.global_loglevel = global;
+        c.module_loglevels = modules.clone();
+    });
+}
+
+pub fn set_format(f: LogFormat) {
+    update_logger_configuration(|c| c.format = f);
+}
+
+pub fn set_error_backtrace(enable: bool) {
+    update_logger_configuration(|c| c.error_backtrace = enable);
+}
+
+pub fn set_forward_to_tracing(enable: bool) {
+    update_logger_configuration(|c| c.forward_to_tracing = enable);
+}
+
+fn forward_enabled() -> bool {
+    CONFIGURATION.load().forward_to_tracing
 }
 
-// TODO,use macros
 pub fn set_global_level_info() {
-    const cc: CellLoggerConfiguration = const_set_loglevel(&LogLevel::Info);
-    unsafe {
-        setup_logger_configuration(&cc);
-    }
+    set_global_level(LogLevel::Info);
 }
 
 pub fn set_error_global_level_info() {
-    const cc: CellLoggerConfiguration = const_set_loglevel(&LogLevel::Error);
-    unsafe {
-        setup_logger_configuration(&cc);
+    set_global_level(LogLevel::Error);
+}
+
+// TODO awful
+fn format_entry(
+    m: &'static dyn Module,
+    file_str: &str,
+    line_no: u32,
+    l: LogLevel,
+    format_msg: &str,
+    fields: &LogFields,
+) -> String {
+    let cfg = CONFIGURATION.load();
+    let backtrace = if l == LogLevel::Error && cfg.error_backtrace {
+        Some(format!("{:?}", Backtrace::new()))
+    } else {
+        None
+    };
+    match cfg.format {
+        LogFormat::Json => match backtrace {
+            Some(bt) => {
+                let mut fields = fields.clone();
+                fields.push(("backtrace", serde_json::Value::from(bt)));
+                json_format_msg(m, file_str, line_no, l, format_msg, &fields)
+            }
+            None => json_format_msg(m, file_str, line_no, l, format_msg, fields),
+        },
+        LogFormat::Console => {
+            let mut msg = String::from(format_msg);
+            if !fields.is_empty() {
+                msg.push_str(",");
+                for (k, v) in fields {
+                    msg.push_str(format!("{}={},", k, v).as_str());
+                }
+            }
+            if let Some(bt) = backtrace {
+                msg.push_str("\n");
+                msg.push_str(bt.as_str());
+            }
+            default_format_msg(m, file_str, line_no, l, msg.as_str())
+        }
     }
 }
 
-// TODO,add black
-// TODO,add module color
-// TODO,add filter & module filter
-// #[cfg(atomic_cas)]
-fn setup_logger_configuration_inner<'a, F>(make_f: F) -> Result<(), CellError>
-where
-    F: FnOnce() -> &'static CellLoggerConfiguration,
-{
-    unsafe {
-        CONFIGURATION = make_f();
+fn short_file_name(file_str: &str) -> String {
+    let mut arrs: Vec<&str> = file_str.split("/").collect();
+    if arrs.len() > SKIP_CALLER {
+        arrs.drain(0..=(arrs.len() - SKIP_CALLER));
+        return arrs.join("/");
     }
-    Ok(())
-    // let old_state = match STATE.compare_exchange(
-    //     UNINITIALIZED,
-    //     INITIALIZING,
-    //     Ordering::SeqCst,
-    //     Ordering::SeqCst,
-    // ) {
-    //     Ok(s) | Err(s) => s,
-    // };
-    // match old_state {
-    //     UNINITIALIZED => {
-    //         unsafe {
-    //             CONFIGURATION = make_f();
-    //         }
-    //         STATE.store(INITIALIZED, Ordering::SeqCst);
-    //         Ok(())
-    //     }
-    //     INITIALIZING => {
-    //         while STATE.load(Ordering::SeqCst) == INITIALIZING {
-    //             std::sync::atomic::spin_loop_hint();
-    //         }
-    //         Err(CellError::new(ERROR_SETUP_FAILED))
-    //     }
-    //     _ => Err(CellError::new((ERROR_SETUP_FAILED))),
-    // }
+    String::from(file_str)
+}
+
+const JSON_RESERVED_KEYS: &'static [&'static str] =
+    &["timestamp", "level", "module", "file", "line", "message"];
+
+// {"timestamp":..,"level":..,"module":..,"file":..,"line":..,"message":..,<fields>}
+fn json_format_msg(
+    m: &'static dyn Module,
+    file_str: &str,
+    line_no: u32,
+    l: LogLevel,
+    format_msg: &str,
+    fields: &LogFields,
+) -> String {
+    let mut obj = serde_json::Map::new();
+    obj.insert(
+        String::from("timestamp"),
+        serde_json::Value::from(Local::now().to_rfc3339()),
+    );
+    obj.insert(
+        String::from("level"),
+        serde_json::Value::from(get_simple_loglevel(l)),
+    );
+    obj.insert(String::from("module"), serde_json::Value::from(m.name()));
+    obj.insert(
+        String::from("file"),
+        serde_json::Value::from(short_file_name(file_str)),
+    );
+    obj.insert(String::from("line"), serde_json::Value::from(line_no));
+    obj.insert(String::from("message"), serde_json::Value::from(format_msg));
+    for (k, v) in fields {
+        // 不覆盖固定字段
+        let key = if JSON_RESERVED_KEYS.contains(k) {
+            format!("field_{}", k)
+        } else {
+            String::from(*k)
+        };
+        obj.insert(key, v.clone());
+    }
+    serde_json::Value::Object(obj).to_string()
 }
 
-// TODO awful
 fn default_format_msg(
     m: &'static dyn Module,
     file_str: &str,
@@ -311,14 +495,8 @@ fn default_format_msg(
 ) -> String {
     // [date] level (module)(file:line)
     let now = get_current_time_str();
-    let mut arrs: Vec<&str> = file_str.split("/").collect();
-    let mut file_info = file_str;
-    let r;
-    if arrs.len() > SKIP_CALLER {
-        arrs.drain(0..=(arrs.len() - SKIP_CALLER));
-        r = arrs.join("/");
-        file_info = r.as_str();
-    }
+    let r = short_file_name(file_str);
+    let file_info = r.as_str();
     let (level_color, module_color) = get_color(l, m.name());
     // format!("[{}] {} ({})({}:{}):{}", now, get_simple_loglevel(l), m.name(), file_info, line_no, format_msg)
     let mut ret = format!(
@@ -333,94 +511,54 @@ fn default_format_msg(
     ret
 }
 
-fn stack_trace<'a>(bt: &'a Backtrace) -> (&str, u32) {
-    let file_str;
-    let line_no;
-    let (file, line) = get_log_info(&bt);
-    match file {
-        None => {
-            file_str = file!();
-            line_no = line!();
-        }
-        Some(v) => {
-            file_str = v;
-            line_no = line.unwrap();
-        }
-    }
-    return (file_str, line_no);
-}
-
 fn get_color(l: LogLevel, module_name: &str) -> (PaintF, PaintF) {
     let level_color;
-    let module_color;
-    unsafe {
-        match l {
-            LogLevel::Trace => level_color = CONFIGURATION.color_property.trace_level_color,
-            LogLevel::Debug => level_color = CONFIGURATION.color_property.debug_level_color,
-            LogLevel::Info => level_color = { CONFIGURATION.color_property.info_level_color },
-            LogLevel::Warn => level_color = { CONFIGURATION.color_property.warn_level_color },
-            LogLevel::Error => level_color = { CONFIGURATION.color_property.error_level_color },
-            _ => level_color = { CONFIGURATION.color_property.info_level_color },
-        }
-        module_color = CONFIGURATION.color_property.default_module_color
+    let cfg = CONFIGURATION.load();
+    match l {
+        LogLevel::Trace => level_color = cfg.color_property.trace_level_color,
+        LogLevel::Debug => level_color = cfg.color_property.debug_level_color,
+        LogLevel::Info => level_color = cfg.color_property.info_level_color,
+        LogLevel::Warn => level_color = cfg.color_property.warn_level_color,
+        LogLevel::Error => level_color = cfg.color_property.error_level_color,
     }
+    let module_color = cfg.color_property.default_module_color;
 
     (level_color, module_color)
 }
 
-// TODO bad code
-// FIXME use macro
-fn get_log_info<'a>(bt: &'a Backtrace) -> (Option<&'a str>, Option<u32>) {
-    let mut v;
-    let mut sp = false;
-    let it = bt.frames().iter();
-    for f in it {
-        sp = false;
-        for s in f.symbols() {
-            match s.filename() {
-                None => continue,
-                Some(f) => unsafe {
-                    let str = f.as_os_str().to_str().unwrap();
-                    for value in CONFIGURATION.black_list.iter() {
-                        v = *value;
-                        if str.contains(v) {
-                            sp = true;
-                            break;
-                        }
-                    }
-                    if sp {
-                        continue;
-                    }
-                    return (f.as_os_str().to_str(), s.lineno());
-                },
-            }
-        }
-    }
-
-    return (None, None);
-}
-
 fn get_current_time_str() -> String {
     let date = Local::now();
     date.format(DATE_FORMAT_STR).to_string()
 }
 
+// cargo runs tests in parallel ,the ones changing or relying on the process wide configuration hold this
+#[cfg(test)]
+pub(crate) fn test_config_lock() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::capture::{CaptureBuffer, CaptureLogger};
+    use crate::common::LogFormat;
     use crate::log::{ColorProperty, Logger, LoggerEntryContext};
     use crate::module::CellModule;
     use crate::{
-        default_format_msg, setup_logger_configuration, CellLoggerConfiguration, LogLevel, Module,
-        PaintF, CONFIGURATION, DATE_FORMAT_STR, DEFAULT_BLACK_LIST, DEFAULT_DEBUG_LEVEL_COLOR,
-        DEFAULT_ERROR_LEVEL_COLOR, DEFAULT_INFO_LEVEL_COLOR, DEFAULT_MODULE_COLOR,
-        DEFAULT_TRACE_LEVEL_COLOR, DEFAULT_WARN_LEVEL_COLOR, GLOBAL_LOGLEVEL,
+        default_format_msg, json_format_msg, logger_configuration, reload, remove_module_level,
+        set_global_level, set_module_level, setup_logger_configuration, test_config_lock,
+        CellLoggerConfiguration, LogLevel, Module, PaintF, DATE_FORMAT_STR,
+        DEFAULT_DEBUG_LEVEL_COLOR, DEFAULT_ERROR_LEVEL_COLOR, DEFAULT_INFO_LEVEL_COLOR,
+        DEFAULT_MODULE_COLOR, DEFAULT_TRACE_LEVEL_COLOR, DEFAULT_WARN_LEVEL_COLOR,
     };
     use ansi_term::ANSIGenericString;
     use ansi_term::Color::Red;
     use chrono::Local;
     use log::error;
     use std::borrow::Borrow;
+    use std::collections::HashMap;
     use std::ops::Deref;
+    use std::thread;
     use std::time::SystemTime;
 
     #[test]
@@ -443,6 +581,28 @@ mod tests {
         println!("{}", msg);
     }
 
+    #[test]
+    fn test_json_format() {
+        static m1: &CellModule = &CellModule::new(1, "JSON", &LogLevel::Info);
+        let fields = vec![
+            ("user_id", serde_json::Value::from(42)),
+            ("cached", serde_json::Value::from(true)),
+            ("level", serde_json::Value::from("custom")),
+        ];
+        let line = json_format_msg(m1, "a/b/c/d.rs", 7, LogLevel::Warn, "hello", &fields);
+        let v: serde_json::Value = serde_json::from_str(line.as_str()).unwrap();
+        assert_eq!(v["level"], "WARN");
+        assert_eq!(v["module"], "JSON");
+        assert_eq!(v["file"], "c/d.rs");
+        assert_eq!(v["line"], 7);
+        assert_eq!(v["message"], "hello");
+        assert_eq!(v["user_id"], 42);
+        assert_eq!(v["cached"], true);
+        assert_eq!(v["field_level"], "custom");
+        assert!(v["timestamp"].is_string());
+        assert!(!line.contains('\n'));
+    }
+
     #[test]
     fn test_create_entry() {
         static m2: &CellModule = &CellModule::new(1, "M2", &LogLevel::Info);
@@ -451,6 +611,17 @@ mod tests {
         println!("{:?}", entry)
     }
 
+    #[test]
+    fn test_caller_location() {
+        static M: &CellModule = &CellModule::new(1, "CALLER", &LogLevel::Info);
+        let buffer = CaptureBuffer::default();
+        let logger = Logger::new(Box::new(CaptureLogger::new(buffer.clone())));
+        let line = line!() + 1;
+        logger.error(M, String::from("located"));
+        let msg = buffer.entries().pop().unwrap().msg;
+        assert!(msg.contains(format!("lib.rs:{}", line).as_str()), "{}", msg);
+    }
+
     #[test]
     fn test_color() {
         let v: ANSIGenericString<str> = Red.paint("asd");
@@ -465,26 +636,85 @@ mod tests {
         println!("{}", ret);
     }
 
+    // the configuration is process wide ,so everything touching it lives in one test
     #[test]
     fn test_change_cfg() {
-        // unsafe {
-        //     println!("{:?}", CONFIGURATION.global_loglevel)
-        // }
-        // static cfg: CellLoggerConfiguration = CellLoggerConfiguration::const_new_default();
-        // setup_logger_configuration(&cfg);
-        // unsafe {
-        //     println!("{:?}", CONFIGURATION.global_loglevel)
-        // }
+        static M: &CellModule = &CellModule::new(1, "CFG", &LogLevel::Info);
+        let _lock = test_config_lock();
+        let saved = logger_configuration();
+        setup_logger_configuration(
+            CellLoggerConfiguration::default().with_global_loglevel(LogLevel::Warn),
+        );
+        assert_eq!(logger_configuration().global_loglevel, LogLevel::Warn);
+        assert_eq!(logger_configuration().get_loglevel(M), LogLevel::Warn);
+
+        set_module_level("CFG", LogLevel::Debug);
+        assert_eq!(logger_configuration().get_loglevel(M), LogLevel::Debug);
+        remove_module_level("CFG");
+        assert_eq!(logger_configuration().get_loglevel(M), LogLevel::Warn);
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| thread::spawn(move || set_module_level(&format!("M{}", i), LogLevel::Error)))
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(logger_configuration().module_loglevels.len(), 8);
+
+        let mut modules = HashMap::new();
+        modules.insert(String::from("CFG"), LogLevel::Trace);
+        reload(LogLevel::Error, modules);
+        let cfg = logger_configuration();
+        assert_eq!(cfg.global_loglevel, LogLevel::Error);
+        assert_eq!(cfg.module_loglevels.len(), 1);
+        assert_eq!(cfg.get_loglevel(M), LogLevel::Trace);
+
+        set_global_level(LogLevel::Info);
+        assert_eq!(logger_configuration().global_loglevel, LogLevel::Info);
+        setup_logger_configuration(CellLoggerConfiguration::clone(&saved));
+    }
+
+    #[test]
+    fn test_module_loglevel() {
+        static DISPATCH: &CellModule = &CellModule::new(1, "HTTP_DISPATCH", &LogLevel::Info);
+        static SERVER: &CellModule = &CellModule::new(2, "HTTP_SERVER", &LogLevel::Info);
+        static EXT: &CellModule = &CellModule::new(3, "EXTENSION", &LogLevel::Info);
+        static NOISY: &CellModule = &CellModule::new(4, "NOISY", &LogLevel::Error);
+
+        let cfg = CellLoggerConfiguration::default()
+            .with_global_loglevel(LogLevel::Info)
+            .with_module_loglevel("HTTP_*", LogLevel::Warn)
+            .with_module_loglevel("HTTP_DISP*", LogLevel::Error)
+            .with_module_loglevel("HTTP_SERVER", LogLevel::Trace)
+            .with_module_loglevel("EXTENSION", LogLevel::Debug);
+        assert_eq!(cfg.get_loglevel(DISPATCH), LogLevel::Error);
+        assert_eq!(cfg.get_loglevel(SERVER), LogLevel::Trace);
+        assert_eq!(cfg.get_loglevel(EXT), LogLevel::Debug);
+        // the module's own level only ever raises the global one
+        assert_eq!(cfg.get_loglevel(NOISY), LogLevel::Error);
+        assert!(cfg.enabled(EXT, LogLevel::Debug));
+        assert!(!cfg.enabled(DISPATCH, LogLevel::Warn));
+
+        let cfg = cfg.with_global_loglevel(LogLevel::Warn);
+        static OTHER: &CellModule = &CellModule::new(5, "OTHER", &LogLevel::Info);
+        assert_eq!(cfg.get_loglevel(OTHER), LogLevel::Warn);
     }
 
     #[test]
     fn test_change_loglevel() {
-        // unsafe {
-        //     println!("{:?}", CONFIGURATION.global_loglevel);
-        // }
-        // set_global_loglevel(&LogLevel::Error);
-        // unsafe {
-        //     println!("{:?}", CONFIGURATION.global_loglevel);
-        // }
+        assert_eq!("warn".parse::<LogLevel>(), Ok(LogLevel::Warn));
+        assert_eq!("ERROR".parse::<LogLevel>(), Ok(LogLevel::Error));
+        assert!("fatal".parse::<LogLevel>().is_err());
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!("JSON".parse::<LogFormat>(), Ok(LogFormat::Json));
+        assert_eq!("console".parse::<LogFormat>(), Ok(LogFormat::Console));
+        let e = "xml".parse::<LogFormat>().unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "unknown log format:xml ,expected console or json"
+        );
     }
 }
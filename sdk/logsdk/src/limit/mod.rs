@@ -0,0 +1,276 @@
+// 按调用点限流/采样: 每个宏调用点一个 static SiteLimiter ,被丢弃的条数定期汇总输出
+use crate::common::LogLevel;
+use crate::log4rs::DEFAULT_LOGGER;
+use crate::module::Module;
+use lazy_static::lazy_static;
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+const UNSET: u64 = u64::MAX;
+
+lazy_static! {
+    static ref START: Instant = Instant::now();
+    static ref SITES: Mutex<Vec<Site>> = Mutex::new(Vec::new());
+}
+
+static REPORTER: Once = Once::new();
+
+thread_local! {
+    static SEED: Cell<u64> = Cell::new(seed());
+}
+
+struct Site {
+    limiter: &'static SiteLimiter,
+    module: &'static dyn Module,
+    level: LogLevel,
+    file: &'static str,
+    line: u32,
+}
+
+pub struct SiteLimiter {
+    // 0 means no rate limit
+    max: u64,
+    interval_millis: u64,
+    // 1.0 keeps everything
+    sample_rate: f64,
+    window_start: AtomicU64,
+    count: AtomicU64,
+    suppressed: AtomicU64,
+    registered: AtomicBool,
+}
+
+impl SiteLimiter {
+    // at most `max` entries per `interval_millis`
+    pub const fn rate(max: u64, interval_millis: u64) -> Self {
+        Self::new(max, interval_millis, 1.0)
+    }
+
+    // keeps roughly `rate` (0.0 ~ 1.0) of the entries
+    pub const fn sampled(rate: f64) -> Self {
+        Self::new(0, 0, rate)
+    }
+
+    const fn new(max: u64, interval_millis: u64, sample_rate: f64) -> Self {
+        SiteLimiter {
+            max,
+            interval_millis,
+            sample_rate,
+            window_start: AtomicU64::new(UNSET),
+            count: AtomicU64::new(0),
+            suppressed: AtomicU64::new(0),
+            registered: AtomicBool::new(false),
+        }
+    }
+
+    pub fn allow(
+        &'static self,
+        m: &'static dyn Module,
+        l: LogLevel,
+        file: &'static str,
+        line: u32,
+    ) -> bool {
+        self.register(m, l, file, line);
+        let (pass, new_window) = self.check_rate(now_millis());
+        let pass = pass && self.check_sample();
+        if !pass {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        if new_window {
+            report(self, m, l, file, line);
+        }
+        true
+    }
+
+    // returns (allowed ,a new window was opened)
+    fn check_rate(&self, now: u64) -> (bool, bool) {
+        if self.max == 0 {
+            return (true, false);
+        }
+        let start = self.window_start.load(Ordering::Acquire);
+        if start == UNSET || now.saturating_sub(start) >= self.interval_millis {
+            if self
+                .window_start
+                .compare_exchange(start, now, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                self.count.store(1, Ordering::Release);
+                return (true, start != UNSET);
+            }
+        }
+        let c = self.count.fetch_add(1, Ordering::AcqRel) + 1;
+        (c <= self.max, false)
+    }
+
+    fn check_sample(&self) -> bool {
+        if self.sample_rate >= 1.0 {
+            return true;
+        }
+        next_f64() < self.sample_rate
+    }
+
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed.load(Ordering::Relaxed)
+    }
+
+    fn register(&'static self, m: &'static dyn Module, l: LogLevel, file: &'static str, line: u32) {
+        if self.registered.load(Ordering::Relaxed)
+            || self
+                .registered
+                .compare_exchange(false, true, Ordering::AcqRel, Ordering::Relaxed)
+                .is_err()
+        {
+            return;
+        }
+        SITES.lock().unwrap().push(Site {
+            limiter: self,
+            module: m,
+            level: l,
+            file,
+            line,
+        });
+        REPORTER.call_once(|| {
+            let _ = thread::Builder::new()
+                .name(String::from("logsdk-limit"))
+                .spawn(|| loop {
+                    thread::sleep(REPORT_INTERVAL);
+                    report_suppressed();
+                });
+        });
+    }
+}
+
+fn report(limiter: &SiteLimiter, m: &'static dyn Module, l: LogLevel, file: &str, line: u32) {
+    let n = limiter.suppressed.swap(0, Ordering::Relaxed);
+    if n == 0 {
+        return;
+    }
+    DEFAULT_LOGGER.log(
+        m,
+        l,
+        file,
+        line,
+        format!("suppressed {} messages from this call site", n).as_str(),
+    );
+}
+
+// 流量停下来之后,最后一个窗口的丢弃数也能输出
+pub fn report_suppressed() {
+    let sites = SITES.lock().unwrap();
+    for s in sites.iter() {
+        report(s.limiter, s.module, s.level, s.file, s.line);
+    }
+}
+
+fn now_millis() -> u64 {
+    START.elapsed().as_millis() as u64
+}
+
+fn seed() -> u64 {
+    let mut h = DefaultHasher::new();
+    thread::current().id().hash(&mut h);
+    SystemTime::now().hash(&mut h);
+    h.finish() | 1
+}
+
+// xorshift ,够用于采样
+fn next_f64() -> f64 {
+    SEED.with(|s| {
+        let mut x = s.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        s.set(x);
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::capture::capture;
+    use crate::common::LogLevel;
+    use crate::limit::{report_suppressed, SiteLimiter};
+    use crate::module::{CellModule, Module};
+    use crate::test_config_lock;
+    use std::sync::atomic::Ordering;
+
+    static M: &CellModule = &CellModule::new(1, "LIMIT", &LogLevel::Info);
+    // only the call sites of test_macros log under it
+    static MACROS: &CellModule = &CellModule::new(1, "LIMIT_MACROS", &LogLevel::Info);
+
+    #[test]
+    fn test_rate() {
+        // report_suppressed in test_macros resets every site
+        let _lock = test_config_lock();
+        static L: SiteLimiter = SiteLimiter::rate(3, 60_000);
+        let passed = (0..10)
+            .filter(|_| L.allow(M, LogLevel::Error, file!(), line!()))
+            .count();
+        assert_eq!(passed, 3);
+        assert_eq!(L.suppressed(), 7);
+
+        // the next window opens once the interval has passed
+        assert_eq!(L.check_rate(u64::MAX / 2), (true, true));
+        // 清零 ,之后的 report_suppressed 不会再输出这个调用点
+        L.suppressed.swap(0, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn test_sampled() {
+        static NONE: SiteLimiter = SiteLimiter::sampled(0.0);
+        static ALL: SiteLimiter = SiteLimiter::sampled(1.0);
+        static HALF: SiteLimiter = SiteLimiter::sampled(0.5);
+        assert!(!(0..100).any(|_| NONE.allow(M, LogLevel::Debug, file!(), line!())));
+        assert!((0..100).all(|_| ALL.allow(M, LogLevel::Debug, file!(), line!())));
+        let kept = (0..10_000)
+            .filter(|_| HALF.allow(M, LogLevel::Trace, file!(), line!()))
+            .count();
+        assert!(kept > 4_000 && kept < 6_000, "{}", kept);
+    }
+
+    #[test]
+    fn test_macros() {
+        let _lock = test_config_lock();
+        let c = capture();
+        for i in 0..5 {
+            cerror_limited!(MACROS, 2, 60_000, "flood {}", i);
+            cdebug_sampled!(MACROS, 0.5, "sampled {}", i);
+        }
+        for attempt in 1..3 {
+            cwarn_limited!(MACROS, 1, 60_000, "kv", { attempt: attempt });
+        }
+
+        assert_eq!(c.find("LIMIT_MACROS", LogLevel::Error, "flood").len(), 2);
+        c.assert_logged("LIMIT_MACROS", LogLevel::Error, "flood 0");
+        c.assert_logged("LIMIT_MACROS", LogLevel::Error, "flood 1");
+        // debug 在 Info 的 module 上被 level 过滤掉 ,不参与采样
+        c.assert_not_logged("LIMIT_MACROS", LogLevel::Debug, "sampled");
+        let kv = c.find("LIMIT_MACROS", LogLevel::Warn, "kv");
+        assert_eq!(kv.len(), 1);
+        assert_eq!(kv[0].fields, vec![("attempt", serde_json::Value::from(1))]);
+
+        report_suppressed();
+        c.assert_logged(
+            "LIMIT_MACROS",
+            LogLevel::Error,
+            "suppressed 3 messages from this call site",
+        );
+        c.assert_logged(
+            "LIMIT_MACROS",
+            LogLevel::Warn,
+            "suppressed 1 messages from this call site",
+        );
+        let own = c
+            .entries()
+            .into_iter()
+            .filter(|e| e.module.name() == "LIMIT_MACROS")
+            .count();
+        assert_eq!(own, 5);
+    }
+}
@@ -42,20 +42,81 @@ macro_rules! cerror {
     };
 }
 
+// 每个调用点最多 $n 条 / $interval_ms 毫秒 ,被丢弃的条数会汇总输出
+#[macro_export]
+macro_rules! cinfo_limited {
+    ($m:expr,$n:expr,$interval_ms:expr, $($rest:tt)*) => {
+        $crate::limited_impl!($m,$crate::common::LogLevel::Info,$crate::limit::SiteLimiter::rate($n,$interval_ms), $($rest)*)
+    };
+}
+
+#[macro_export]
+macro_rules! cwarn_limited {
+    ($m:expr,$n:expr,$interval_ms:expr, $($rest:tt)*) => {
+        $crate::limited_impl!($m,$crate::common::LogLevel::Warn,$crate::limit::SiteLimiter::rate($n,$interval_ms), $($rest)*)
+    };
+}
+
+#[macro_export]
+macro_rules! cerror_limited {
+    ($m:expr,$n:expr,$interval_ms:expr, $($rest:tt)*) => {
+        $crate::limited_impl!($m,$crate::common::LogLevel::Error,$crate::limit::SiteLimiter::rate($n,$interval_ms), $($rest)*)
+    };
+}
+
+// 按比例采样 ,$rate 取 0.0 ~ 1.0
+#[macro_export]
+macro_rules! cdebug_sampled {
+    ($m:expr,$rate:expr, $($rest:tt)*) => {
+        $crate::limited_impl!($m,$crate::common::LogLevel::Debug,$crate::limit::SiteLimiter::sampled($rate), $($rest)*)
+    };
+}
+
+#[macro_export]
+macro_rules! ctrace_sampled {
+    ($m:expr,$rate:expr, $($rest:tt)*) => {
+        $crate::limited_impl!($m,$crate::common::LogLevel::Trace,$crate::limit::SiteLimiter::sampled($rate), $($rest)*)
+    };
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! limited_impl {
+    ($m:expr,$lvl:expr,$limiter:expr,$e:expr) => {{
+        static LIMITER: $crate::limit::SiteLimiter = $limiter;
+        if $crate::enabled($m,$lvl) && LIMITER.allow($m,$lvl,file!(),line!()) {
+            $crate::log_impl!($m,$lvl,($e));
+        }
+    }};
+
+    ($m:expr,$lvl:expr,$limiter:expr,$e:expr, $($rest:tt)*) => {{
+        static LIMITER: $crate::limit::SiteLimiter = $limiter;
+        if $crate::enabled($m,$lvl) && LIMITER.allow($m,$lvl,file!(),line!()) {
+            $crate::log_impl!($m,$lvl,($e) $($rest)*);
+        }
+    }};
+}
+
 #[macro_export]
 #[doc(hidden)]
 macro_rules! log_impl {
+    // 先判断 level ,被过滤掉的日志不做 format
     ($m:expr,$lvl:expr,($($e:expr),*)) => {
-        $crate::log4rs::DEFAULT_LOGGER.log($m,$lvl,file!(),line!(),format!("{}",format!($($e),*)).as_str())
+        if $crate::enabled($m,$lvl) {
+            $crate::log4rs::DEFAULT_LOGGER.log($m,$lvl,file!(),line!(),format!("{}",format!($($e),*)).as_str())
+        }
     };
 
     ($m:expr,$lvl:expr,($($e:expr),*) { $($key:ident : $value:expr),* }) => {
-        let mut msg=format!("{}",format!($($e),*));
-        msg.push_str(",");
-        $(
-            msg.push_str(format!("{}={:?},",stringify!($key), $value).as_str());
-        )*
-        $crate::log4rs::DEFAULT_LOGGER.log($m,$lvl,file!(),line!(),msg.as_str())
+        if $crate::enabled($m,$lvl) {
+            let msg=format!("{}",format!($($e),*));
+            let fields:$crate::common::LogFields=vec![
+                $(
+                    (stringify!($key),$crate::serde_json::to_value(&$value).unwrap_or($crate::serde_json::Value::Null)),
+                )*
+            ];
+            $crate::log4rs::DEFAULT_LOGGER.log_with_fields($m,$lvl,file!(),line!(),msg.as_str(),fields)
+        }
     };
 
     ($m:expr,$lvl:expr,($($e:expr),*) { $($key:ident : $value:expr,)* }) => {
@@ -2,10 +2,12 @@
 pub mod cmacro;
 
 use crate::common::{LogEntry, LogLevel};
+use crate::file::append_to_files;
 use crate::log::{Logger, MLogger};
 use crate::log4rs::log_config::{setup_by_name, AppenderProperty};
 use crate::module::{CellModule, Module};
-use crate::{CONFIGURATION, DEFAULT_MODULE};
+use crate::writer::dispatch;
+use crate::{enabled, DEFAULT_MODULE};
 use lazy_static::lazy_static;
 use log::{info, Log, RecordBuilder};
 use log4rs::append::console::ConsoleAppender;
@@ -20,7 +22,7 @@ lazy_static! {
 }
 
 pub struct Log4rsLogger {
-    log4rs: log4rs::Logger,
+    log4rs: Arc<log4rs::Logger>,
 }
 
 impl MLogger for Log4rsLogger {
@@ -31,12 +33,17 @@ impl MLogger for Log4rsLogger {
 
 impl Log4rsLogger {
     fn loglevel_to_log4rs(&self, entry: LogEntry) {
-        let level;
-        unsafe {
-            if CONFIGURATION.global_loglevel.gt(entry.log_level) {
-                return;
-            }
+        if !enabled(entry.module, entry.log_level) {
+            return;
         }
+        // 开启了 async writer 时在后台线程写入,否则直接在当前线程写
+        let log4rs = self.log4rs.clone();
+        dispatch(move || Self::write(&log4rs, entry));
+    }
+
+    fn write(log4rs: &log4rs::Logger, entry: LogEntry) {
+        let level;
+        append_to_files(&entry);
         match entry.log_level {
             LogLevel::Trace => {
                 level = log::Level::Trace;
@@ -54,7 +61,7 @@ impl Log4rsLogger {
                 level = log::Level::Error;
             }
         }
-        self.log4rs.log(
+        log4rs.log(
             &log::Record::builder()
                 .level(level)
                 .args(format_args!("{}", entry.msg))
@@ -66,7 +73,9 @@ impl Log4rsLogger {
         let level = m.log_level();
         let cfg = setup_by_name(module_name, level);
         let lg4 = log4rs::Logger::new(cfg);
-        let mut ret = Log4rsLogger { log4rs: lg4 };
+        let mut ret = Log4rsLogger {
+            log4rs: Arc::new(lg4),
+        };
         ret
     }
 }
@@ -143,6 +152,10 @@ mod log_config {
                 level = Level::Trace;
                 level_filter = LevelFilter::Trace
             }
+            LogLevel::Debug => {
+                level = Level::Debug;
+                level_filter = LevelFilter::Debug
+            }
             LogLevel::Info => {
                 level = Level::Info;
                 level_filter = LevelFilter::Info
@@ -152,12 +165,8 @@ mod log_config {
                 level_filter = LevelFilter::Warn
             }
             LogLevel::Error => {
-                level = Level::Warn;
-                level_filter = LevelFilter::Warn
-            }
-            _ => {
-                level = Level::Warn;
-                level_filter = LevelFilter::Warn
+                level = Level::Error;
+                level_filter = LevelFilter::Error
             }
         }
 
@@ -188,13 +197,11 @@ mod tests {
     use crate::log4rs::{Log4rsLogger, DEFAULT_LOGGER};
     use crate::module::{CellModule, Module};
     use crate::{
-        module, setup_logger_configuration, stack_trace, CellLoggerConfiguration, ColorProperty,
-        PaintF, CONFIGURATION, DEFAULT_BLACK_LIST, DEFAULT_DEBUG_LEVEL_COLOR,
-        DEFAULT_ERROR_LEVEL_COLOR, DEFAULT_INFO_LEVEL_COLOR, DEFAULT_MODULE_COLOR,
-        DEFAULT_TRACE_LEVEL_COLOR, DEFAULT_WARN_LEVEL_COLOR,
+        module, setup_logger_configuration, CellLoggerConfiguration, ColorProperty, PaintF,
+        DEFAULT_DEBUG_LEVEL_COLOR, DEFAULT_ERROR_LEVEL_COLOR, DEFAULT_INFO_LEVEL_COLOR,
+        DEFAULT_MODULE_COLOR, DEFAULT_TRACE_LEVEL_COLOR, DEFAULT_WARN_LEVEL_COLOR,
     };
     use ansi_term::Color::Red;
-    use backtrace::Backtrace;
     use lazy_static::lazy_static;
     use log::{info, log};
     use phf::phf_map;
@@ -206,9 +213,8 @@ mod tests {
     fn test_log() {
         static m: &CellModule = &module::CellModule::new(1, "asd", &LogLevel::Info);
         let l = Log4rsLogger::new(m);
-        let bt = Backtrace::new();
-        let (s, line) = stack_trace(&bt);
-        let entry = LoggerEntryContext::create_log_entry(m, LogLevel::Info, s, line, "asdddd");
+        let entry =
+            LoggerEntryContext::create_log_entry(m, LogLevel::Info, file!(), line!(), "asdddd");
         l.log(entry);
     }
 
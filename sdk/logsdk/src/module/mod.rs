@@ -7,6 +7,7 @@ pub trait Module: Display + Debug + Sync {
     fn log_level(&self) -> &'static LogLevel;
 }
 
+#[derive(Clone)]
 pub struct CellModule {
     index: i16,
     name: &'static str,
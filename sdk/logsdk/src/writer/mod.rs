@@ -0,0 +1,306 @@
+// 异步输出: 调用方只把日志放进有界队列,由后台线程负责真正的写入
+use crate::common::LogLevel;
+use crate::file::flush_files;
+use crate::module::CellModule;
+use arc_swap::ArcSwapOption;
+use crossbeam::queue::ArrayQueue;
+use lazy_static::lazy_static;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+static LOGSDK: &CellModule = &CellModule::new(1, "LOGSDK", &LogLevel::Info);
+
+const IDLE_PARK: Duration = Duration::from_millis(10);
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    static ref ASYNC_WRITER: ArcSwapOption<AsyncWriter> = ArcSwapOption::from(None);
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    // the caller waits for room ,nothing is lost
+    Block,
+    // the entry is discarded and counted
+    Drop,
+}
+
+#[derive(Clone, Debug)]
+pub struct AsyncWriterProperty {
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for AsyncWriterProperty {
+    fn default() -> Self {
+        AsyncWriterProperty {
+            capacity: 8192,
+            overflow: OverflowPolicy::Block,
+        }
+    }
+}
+
+impl AsyncWriterProperty {
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+    pub fn with_overflow(mut self, overflow: OverflowPolicy) -> Self {
+        self.overflow = overflow;
+        self
+    }
+}
+
+pub struct AsyncWriter {
+    queue: ArrayQueue<Job>,
+    overflow: OverflowPolicy,
+    // pushed but not yet written
+    pending: AtomicUsize,
+    dropped: AtomicU64,
+    reported: AtomicU64,
+    running: AtomicBool,
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl AsyncWriter {
+    fn start(property: AsyncWriterProperty) -> Arc<Self> {
+        let writer = Arc::new(AsyncWriter {
+            queue: ArrayQueue::new(property.capacity.max(1)),
+            overflow: property.overflow,
+            pending: AtomicUsize::new(0),
+            dropped: AtomicU64::new(0),
+            reported: AtomicU64::new(0),
+            running: AtomicBool::new(true),
+            worker: Mutex::new(None),
+        });
+        let w = writer.clone();
+        let handle = thread::Builder::new()
+            .name(String::from("logsdk-writer"))
+            .spawn(move || w.run())
+            .expect("spawn log writer failed");
+        *writer.worker.lock().unwrap() = Some(handle);
+        writer
+    }
+
+    fn run(&self) {
+        loop {
+            match self.queue.pop() {
+                Some(job) => {
+                    job();
+                    self.pending.fetch_sub(1, Ordering::SeqCst);
+                }
+                None => {
+                    self.report_dropped();
+                    if !self.running.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    thread::park_timeout(IDLE_PARK);
+                }
+            }
+        }
+    }
+
+    fn push(&self, job: Job) {
+        // dispatch 可能拿到一个刚被换下并 stop 的 writer ,这时在调用线程直接写
+        if !self.running.load(Ordering::SeqCst) {
+            job();
+            return;
+        }
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        let mut job = job;
+        loop {
+            match self.queue.push(job) {
+                Ok(()) => {
+                    // stop 在入队之后发生 ,worker 可能已经退出
+                    if !self.running.load(Ordering::SeqCst) {
+                        self.drain();
+                    }
+                    return;
+                }
+                Err(back) if !self.running.load(Ordering::SeqCst) => {
+                    self.drain();
+                    back();
+                    self.pending.fetch_sub(1, Ordering::SeqCst);
+                    return;
+                }
+                Err(back) => match self.overflow {
+                    OverflowPolicy::Drop => {
+                        self.pending.fetch_sub(1, Ordering::SeqCst);
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                        return;
+                    }
+                    OverflowPolicy::Block => {
+                        job = back;
+                        self.wake();
+                        thread::yield_now();
+                    }
+                },
+            }
+        }
+    }
+
+    fn wake(&self) {
+        if let Some(h) = self.worker.lock().unwrap().as_ref() {
+            h.thread().unpark();
+        }
+    }
+
+    // 只在队列空闲时汇报 ,避免在拥塞时继续加压
+    fn report_dropped(&self) {
+        let dropped = self.dropped.load(Ordering::Relaxed);
+        let reported = self.reported.swap(dropped, Ordering::Relaxed);
+        if dropped > reported {
+            cwarn!(
+                LOGSDK,
+                "log queue is full ,dropped {} entries ({} in total)",
+                dropped - reported,
+                dropped
+            );
+        }
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn flush(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.pending.load(Ordering::SeqCst) > 0 {
+            if Instant::now() > deadline {
+                return false;
+            }
+            self.wake();
+            thread::sleep(Duration::from_millis(1));
+        }
+        true
+    }
+
+    fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+        self.wake();
+        if let Some(h) = self.worker.lock().unwrap().take() {
+            let _ = h.join();
+        }
+        // worker 退出前后才入队的
+        self.drain();
+    }
+
+    fn drain(&self) {
+        while let Some(job) = self.queue.pop() {
+            job();
+            self.pending.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+// 重复调用会先关闭之前的 writer
+pub fn start_async_writer(property: AsyncWriterProperty) {
+    let old = ASYNC_WRITER.swap(Some(AsyncWriter::start(property)));
+    if let Some(old) = old {
+        old.flush(FLUSH_TIMEOUT);
+        old.stop();
+    }
+}
+
+// 之后的日志回到调用线程同步输出
+pub fn shutdown_async_writer() {
+    if let Some(w) = ASYNC_WRITER.swap(None) {
+        w.flush(FLUSH_TIMEOUT);
+        w.stop();
+    }
+    flush_files();
+}
+
+// waits until every queued entry is written ,returns false on timeout
+pub fn flush() -> bool {
+    let ret = match ASYNC_WRITER.load().as_ref() {
+        Some(w) => w.flush(FLUSH_TIMEOUT),
+        None => true,
+    };
+    flush_files();
+    ret
+}
+
+pub fn dropped_count() -> u64 {
+    ASYNC_WRITER.load().as_ref().map_or(0, |w| w.dropped())
+}
+
+pub fn dispatch<F>(f: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    match ASYNC_WRITER.load().as_ref() {
+        Some(w) => w.push(Box::new(f)),
+        None => f(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::writer::{AsyncWriter, AsyncWriterProperty, OverflowPolicy};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Barrier};
+    use std::time::Duration;
+
+    #[test]
+    fn test_block_policy() {
+        let w = AsyncWriter::start(AsyncWriterProperty::default().with_capacity(4));
+        let written = Arc::new(AtomicUsize::new(0));
+        for _ in 0..100 {
+            let c = written.clone();
+            w.push(Box::new(move || {
+                c.fetch_add(1, Ordering::SeqCst);
+            }));
+        }
+        assert!(w.flush(Duration::from_secs(5)));
+        assert_eq!(written.load(Ordering::SeqCst), 100);
+        assert_eq!(w.dropped(), 0);
+        w.stop();
+    }
+
+    #[test]
+    fn test_drop_policy() {
+        let w = AsyncWriter::start(
+            AsyncWriterProperty::default()
+                .with_capacity(2)
+                .with_overflow(OverflowPolicy::Drop),
+        );
+        // 卡住后台线程,让队列被填满
+        let gate = Arc::new(Barrier::new(2));
+        let g = gate.clone();
+        w.push(Box::new(move || {
+            g.wait();
+        }));
+        while w.queue.len() > 0 {
+            std::thread::yield_now();
+        }
+        for _ in 0..5 {
+            w.push(Box::new(|| {}));
+        }
+        assert_eq!(w.dropped(), 3);
+        gate.wait();
+        assert!(w.flush(Duration::from_secs(5)));
+        w.stop();
+    }
+
+    #[test]
+    fn test_push_after_stop() {
+        let w = AsyncWriter::start(AsyncWriterProperty::default().with_capacity(1));
+        w.stop();
+        // 没有 worker 了 ,也不能丢或者卡住
+        let written = Arc::new(AtomicUsize::new(0));
+        for _ in 0..3 {
+            let c = written.clone();
+            w.push(Box::new(move || {
+                c.fetch_add(1, Ordering::SeqCst);
+            }));
+        }
+        assert_eq!(written.load(Ordering::SeqCst), 3);
+        assert!(w.flush(Duration::from_millis(10)));
+    }
+}